@@ -0,0 +1,95 @@
+// Compares decoding every entry of a large dump with going through a
+// `ParseCache`, the way a poller that dumps the table every few seconds
+// would. Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Instant;
+
+use netlink_packet_core::NetlinkMessage;
+use serialization_deserialization_netlink_rs::NetlinkStreamReader;
+use serialization_deserialization_netlink_rs::conntrack::cache::ParseCache;
+use serialization_deserialization_netlink_rs::conntrack::{
+    AF_INET, ConntrackAttribute, FiveTuple, IPPROTO_TCP, NetfilterMessage, Nfgenmsg, ProtoInfo,
+    ProtoInfoTCP,
+};
+
+const ENTRIES: u32 = 10_000;
+const ROUNDS: u32 = 20;
+
+// The messages of a dump of `ENTRIES` established TCP connections, each one
+// as its own buffer. Every dump answers a new request, so its messages carry
+// their own `sequence_number`.
+fn dump(sequence_number: u32) -> Vec<Vec<u8>> {
+    let mut stream = NetlinkStreamReader::new();
+    for id in 0..ENTRIES {
+        let orig = FiveTuple {
+            src: IpAddr::V4(Ipv4Addr::from(0x0a00_0000 + id)),
+            dst: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+            protocol: IPPROTO_TCP,
+            src_port: Some(1024 + (id % 60000) as u16),
+            dst_port: Some(443),
+        };
+        let mut packet = NetlinkMessage::from(NetfilterMessage::conntrack_new(
+            Nfgenmsg {
+                nfgen_family: AF_INET,
+                version: 0,
+                resource_id: 0,
+            },
+            vec![
+                ConntrackAttribute::CtaTupleOrig(orig.to_tuples()),
+                ConntrackAttribute::CtaTupleReply(orig.reply().to_tuples()),
+                ConntrackAttribute::CtaStatus(0x19e),
+                ConntrackAttribute::CtaMark(0),
+                ConntrackAttribute::CtaId(id),
+                ConntrackAttribute::CtaTimeout(431999),
+                ConntrackAttribute::CtaProtoInfo(vec![ProtoInfo::TCP(vec![ProtoInfoTCP::State(
+                    3,
+                )])]),
+            ],
+        ));
+        packet.header.sequence_number = sequence_number;
+        packet.finalize();
+        let mut buf = vec![0; packet.buffer_len()];
+        packet.serialize(&mut buf);
+        stream.push(&buf);
+    }
+
+    let mut messages = Vec::new();
+    while let Some(raw) = stream.next_raw().unwrap() {
+        messages.push(raw);
+    }
+    messages
+}
+
+fn time(name: &str, dumps: &[Vec<Vec<u8>>], mut parse: impl FnMut(&[u8])) {
+    let start = Instant::now();
+    for dump in dumps {
+        for raw in dump {
+            parse(raw);
+        }
+    }
+    let per_round = start.elapsed() / ROUNDS;
+    println!(
+        "{:<10} {:>10.3?} per dump, {:>10.3?} per entry",
+        name,
+        per_round,
+        per_round / ENTRIES
+    );
+}
+
+fn main() {
+    let dumps: Vec<_> = (1..=ROUNDS).map(dump).collect();
+
+    time("uncached", &dumps, |raw| {
+        black_box(NetlinkMessage::<NetfilterMessage>::deserialize(raw).unwrap());
+    });
+
+    // Every round after the first one finds the same entries again, as when
+    // polling a table whose entries did not change.
+    let mut cache = ParseCache::new();
+    time("cached", &dumps, |raw| {
+        black_box(cache.parse(raw).unwrap());
+    });
+    println!("cache: {} hits, {} misses", cache.hits, cache.misses);
+}
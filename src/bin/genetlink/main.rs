@@ -0,0 +1,245 @@
+// Generic netlink (NETLINK_GENERIC) example.
+// Every generic netlink message starts with a 4 byte `genlmsghdr`:
+// - `cmd`: the command understood by the family
+// - `version`: the version of the family's interface
+// - `reserved`: always zero
+
+use netlink_packet_core::{
+    DecodeError, DefaultNla, Emitable, ErrorContext, NLM_F_REQUEST, NetlinkDeserializable,
+    NetlinkHeader, NetlinkMessage, NetlinkPayload, NetlinkSerializable, Nla, NlaBuffer,
+    NlasIterator, Parseable, buffer, emit_u16, fields, getter, parse_string, parse_u16, setter,
+};
+use serialization_deserialization_netlink_rs::{attributes_len, emit_header_and_nlas};
+
+pub const GENL_HDRLEN: usize = 4;
+
+// We use the `buffer!` macro to create a safe, zero-copy wrapper around a byte slice.
+// It automatically generates getter and setter methods for the fields we define.
+buffer!(GenlMsgHeaderBuffer(GENL_HDRLEN) {
+    cmd: (u8, 0),
+    version: (u8, 1),
+    reserved: (u16, 2..4),
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenlMsgHeader {
+    pub cmd: u8,
+    pub version: u8,
+    pub reserved: u16,
+}
+
+// Implement `Emitable` to define how to write `GenlMsgHeader` to a byte buffer.
+impl Emitable for GenlMsgHeader {
+    fn buffer_len(&self) -> usize {
+        GENL_HDRLEN
+    }
+
+    fn emit(&self, buffer: &mut [u8]) {
+        let mut buf = GenlMsgHeaderBuffer::new(buffer);
+        buf.set_cmd(self.cmd);
+        buf.set_version(self.version);
+        buf.set_reserved(self.reserved);
+    }
+}
+
+// Implement `Parseable` to define how to read `GenlMsgHeader` from a byte buffer.
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<GenlMsgHeaderBuffer<&'a T>> for GenlMsgHeader {
+    fn parse(buf: &GenlMsgHeaderBuffer<&'a T>) -> Result<Self, DecodeError> {
+        Ok(GenlMsgHeader {
+            cmd: buf.cmd(),
+            version: buf.version(),
+            reserved: buf.reserved(),
+        })
+    }
+}
+
+// The controller (`nlctrl`) is the only family with a fixed id. It is used to
+// resolve the dynamically allocated ids of every other family.
+pub const GENL_ID_CTRL: u16 = 0x10;
+
+pub const CTRL_CMD_NEWFAMILY: u8 = 1;
+pub const CTRL_CMD_GETFAMILY: u8 = 3;
+
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+// Only the attributes needed to resolve a family are modeled, the rest of a
+// CTRL_CMD_NEWFAMILY reply (version, ops, multicast groups...) is kept as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CtrlAttribute {
+    FamilyId(u16),
+    FamilyName(String),
+    Other(DefaultNla),
+}
+
+impl Nla for CtrlAttribute {
+    fn value_len(&self) -> usize {
+        match self {
+            Self::FamilyId(v) => size_of_val(v),
+            // +1 for the null terminator
+            Self::FamilyName(s) => s.len() + 1,
+            Self::Other(nla) => nla.value_len(),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Self::FamilyId(_) => CTRL_ATTR_FAMILY_ID,
+            Self::FamilyName(_) => CTRL_ATTR_FAMILY_NAME,
+            Self::Other(nla) => nla.kind(),
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Self::FamilyId(v) => emit_u16(buffer, *v).unwrap(),
+            Self::FamilyName(s) => {
+                buffer[..s.len()].copy_from_slice(s.as_bytes());
+                buffer[s.len()] = 0;
+            }
+            Self::Other(nla) => nla.emit_value(buffer),
+        }
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for CtrlAttribute {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTRL_ATTR_FAMILY_ID => {
+                Self::FamilyId(parse_u16(payload).context("invalid CTRL_ATTR_FAMILY_ID value")?)
+            }
+            CTRL_ATTR_FAMILY_NAME => Self::FamilyName(
+                parse_string(payload).context("invalid CTRL_ATTR_FAMILY_NAME value")?,
+            ),
+            _ => Self::Other(DefaultNla::parse(buf)?),
+        })
+    }
+}
+
+// Top level message of the controller family
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtrlMessage {
+    pub header: GenlMsgHeader,
+    pub nlas: Vec<CtrlAttribute>,
+}
+
+// for serializing
+impl NetlinkSerializable for CtrlMessage {
+    fn message_type(&self) -> u16 {
+        GENL_ID_CTRL
+    }
+
+    fn buffer_len(&self) -> usize {
+        self.header.buffer_len() + attributes_len(&self.nlas)
+    }
+
+    fn serialize(&self, buffer: &mut [u8]) {
+        emit_header_and_nlas(&self.header, &self.nlas, buffer);
+    }
+}
+
+// for deserializing the message
+impl NetlinkDeserializable for CtrlMessage {
+    type Error = DecodeError;
+
+    fn deserialize(header: &NetlinkHeader, payload: &[u8]) -> Result<Self, Self::Error> {
+        if header.message_type != GENL_ID_CTRL {
+            return Err(DecodeError::from(format!(
+                "Unknown message type for the nlctrl family: {}",
+                header.message_type
+            )));
+        }
+
+        let genl_header = GenlMsgHeader::parse(
+            &GenlMsgHeaderBuffer::new_checked(payload).context("invalid genlmsghdr")?,
+        )?;
+
+        let error_msg = "failed to parse nlctrl attributes";
+        let mut nlas = Vec::new();
+        for nla in NlasIterator::new(&payload[GENL_HDRLEN..]) {
+            let nla = &nla.context(error_msg)?;
+            nlas.push(CtrlAttribute::parse(nla)?);
+        }
+
+        Ok(CtrlMessage {
+            header: genl_header,
+            nlas,
+        })
+    }
+}
+
+// to do stuff like `NetlinkMessage::from(my_ctrl_message)`.
+impl From<CtrlMessage> for NetlinkPayload<CtrlMessage> {
+    fn from(message: CtrlMessage) -> Self {
+        NetlinkPayload::InnerMessage(message)
+    }
+}
+
+// Build the finalized CTRL_CMD_GETFAMILY request asking the kernel for the
+// id of the family called `name`.
+pub fn get_family_request(name: &str, sequence_number: u32) -> NetlinkMessage<CtrlMessage> {
+    let request = CtrlMessage {
+        header: GenlMsgHeader {
+            cmd: CTRL_CMD_GETFAMILY,
+            version: 1,
+            reserved: 0,
+        },
+        nlas: vec![CtrlAttribute::FamilyName(name.to_string())],
+    };
+    let mut packet = NetlinkMessage::from(request);
+    packet.header.flags = NLM_F_REQUEST;
+    packet.header.sequence_number = sequence_number;
+    packet.finalize();
+    packet
+}
+
+// Extract the `CTRL_ATTR_FAMILY_ID` from the kernel's reply to
+// `get_family_request()`.
+pub fn parse_family_id(buf: &[u8]) -> Result<u16, DecodeError> {
+    let reply = NetlinkMessage::<CtrlMessage>::deserialize(buf)?;
+    let NetlinkPayload::InnerMessage(message) = reply.payload else {
+        return Err(DecodeError::from(
+            "the reply does not carry a nlctrl message",
+        ));
+    };
+
+    message
+        .nlas
+        .iter()
+        .find_map(|nla| match nla {
+            CtrlAttribute::FamilyId(id) => Some(*id),
+            _ => None,
+        })
+        .ok_or_else(|| DecodeError::from("CTRL_ATTR_FAMILY_ID missing from the reply"))
+}
+
+fn main() {
+    let header = GenlMsgHeader {
+        cmd: 3,
+        version: 2,
+        reserved: 0,
+    };
+
+    let mut buf = vec![0; header.buffer_len()];
+    header.emit(&mut buf);
+
+    println!("Serialized Bytes: {:?}", buf);
+
+    let parsed = GenlMsgHeader::parse(&GenlMsgHeaderBuffer::new(&buf)).unwrap();
+
+    // Verify that the round trip was successful.
+    assert_eq!(header, parsed);
+
+    let packet = get_family_request("nl80211", 1);
+    println!("GETFAMILY request: {:#?}", packet);
+
+    let mut buf = vec![0; packet.buffer_len()];
+    packet.serialize(&mut buf);
+
+    let deserialized_packet = NetlinkMessage::<CtrlMessage>::deserialize(&buf).unwrap();
+    assert_eq!(packet, deserialized_packet);
+}
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,76 @@
+use crate::{
+    CTRL_CMD_NEWFAMILY, CtrlAttribute, CtrlMessage, GenlMsgHeader, GenlMsgHeaderBuffer,
+    get_family_request, parse_family_id,
+};
+use netlink_packet_core::{Emitable, NetlinkMessage, NetlinkPayload, Parseable};
+use serialization_deserialization_netlink_rs::roundtrip;
+
+#[test]
+fn test_genl_header() {
+    let header = GenlMsgHeader {
+        cmd: 1,
+        version: 1,
+        reserved: 0,
+    };
+
+    let mut buf = vec![0; header.buffer_len()];
+    header.emit(&mut buf);
+    assert_eq!(buf, [0x01, 0x01, 0x00, 0x00]);
+
+    let parsed = GenlMsgHeader::parse(&GenlMsgHeaderBuffer::new_checked(&buf).unwrap()).unwrap();
+    assert_eq!(header, parsed);
+}
+
+#[test]
+fn test_get_family_request() {
+    let packet = get_family_request("TASKSTATS", 1);
+
+    let mut buf = vec![0; packet.buffer_len()];
+    packet.serialize(&mut buf);
+
+    // nlmsghdr + genlmsghdr + CTRL_ATTR_FAMILY_NAME("TASKSTATS")
+    let expected: Vec<u8> = vec![
+        0x24, 0x00, 0x00, 0x00, 0x10, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x03, 0x01, 0x00, 0x00, 0x0e, 0x00, 0x02, 0x00, 0x54, 0x41, 0x53, 0x4b, 0x53, 0x54,
+        0x41, 0x54, 0x53, 0x00, 0x00, 0x00,
+    ];
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn test_parse_family_id() {
+    // CTRL_CMD_NEWFAMILY reply for TASKSTATS: id, name, version, hdrsize,
+    // maxattr and a single entry in the ops list.
+    let raw: Vec<u8> = vec![
+        0x5c, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x39, 0x30, 0x00,
+        0x00, 0x01, 0x02, 0x00, 0x00, 0x06, 0x00, 0x01, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x0e, 0x00,
+        0x02, 0x00, 0x54, 0x41, 0x53, 0x4b, 0x53, 0x54, 0x41, 0x54, 0x53, 0x00, 0x00, 0x00, 0x08,
+        0x00, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x08, 0x00, 0x05, 0x00, 0x04, 0x00, 0x00, 0x00, 0x18, 0x00, 0x06, 0x80, 0x14, 0x00, 0x01,
+        0x80, 0x08, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00, 0x02, 0x00, 0x0b, 0x00,
+        0x00, 0x00,
+    ];
+
+    assert_eq!(parse_family_id(&raw).unwrap(), 28);
+
+    // The attributes we don't model must survive a round trip untouched.
+    let reply = NetlinkMessage::<CtrlMessage>::deserialize(&raw).unwrap();
+    let NetlinkPayload::InnerMessage(ref message) = reply.payload else {
+        panic!("expected a nlctrl message");
+    };
+    assert_eq!(message.header.cmd, CTRL_CMD_NEWFAMILY);
+    assert_eq!(
+        message.nlas[1],
+        CtrlAttribute::FamilyName("TASKSTATS".to_string())
+    );
+
+    let mut buf = vec![0; reply.buffer_len()];
+    reply.serialize(&mut buf);
+    assert_eq!(buf, raw);
+}
+
+#[test]
+fn test_roundtrip() {
+    let packet = get_family_request("nl80211", 1);
+    assert_eq!(roundtrip(&packet).unwrap(), packet);
+}
@@ -2,6 +2,7 @@ use netlink_packet_core::{
     Emitable, NetlinkDeserializable, NetlinkHeader, NetlinkMessage, NetlinkPayload,
     NetlinkSerializable, Nla, NlaBuffer, Parseable,
 };
+use serialization_deserialization_netlink_rs::{NetlinkProtocol, StringPolicy};
 use std::error::Error;
 use std::fmt;
 use std::mem::size_of;
@@ -36,7 +37,7 @@ impl Nla for PingPongAttribute {
     // length
     fn value_len(&self) -> usize {
         match self {
-            PingPongAttribute::Message(s) => s.len() + 1, // +1 for null terminator
+            PingPongAttribute::Message(s) => StringPolicy::NullTerminated.value_len(s),
             PingPongAttribute::Cookie(_) => size_of::<u32>(),
         }
     }
@@ -50,10 +51,7 @@ impl Nla for PingPongAttribute {
     // value
     fn emit_value(&self, buffer: &mut [u8]) {
         match self {
-            PingPongAttribute::Message(s) => {
-                buffer[..s.len()].copy_from_slice(s.as_bytes());
-                buffer[s.len()] = 0;
-            }
+            PingPongAttribute::Message(s) => StringPolicy::NullTerminated.emit(s, buffer),
             PingPongAttribute::Cookie(n) => buffer.copy_from_slice(&n.to_ne_bytes()),
         }
     }
@@ -159,6 +157,26 @@ impl NetlinkSerializable for PingPongMessage {
     }
 }
 
+// Human readable form, e.g. `cookie=129`.
+impl fmt::Display for PingPongAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PingPongAttribute::Message(s) => write!(f, "message={}", s),
+            PingPongAttribute::Cookie(n) => write!(f, "cookie={}", n),
+        }
+    }
+}
+
+// Human readable form, e.g. `Ping cookie=129`.
+impl fmt::Display for PingPongMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PingPongMessage::Ping(attr) => write!(f, "Ping {}", attr),
+            PingPongMessage::Pong(attr) => write!(f, "Pong {}", attr),
+        }
+    }
+}
+
 // It can be convenient to be able to create a NetlinkMessage directly
 // from a PingPongMessage. Since NetlinkMessage<T> already implements
 // From<NetlinkPayload<T>>, we just need to implement
@@ -169,6 +187,18 @@ impl From<PingPongMessage> for NetlinkPayload<PingPongMessage> {
     }
 }
 
+impl NetlinkProtocol for PingPongMessage {
+    fn owns_message_type(message_type: u16) -> bool {
+        matches!(message_type, PING_MESSAGE | PONG_MESSAGE)
+    }
+
+    fn deserialize(
+        buf: &[u8],
+    ) -> std::result::Result<NetlinkMessage<Self>, netlink_packet_core::DecodeError> {
+        NetlinkMessage::deserialize(buf)
+    }
+}
+
 fn main() {
     let ping_pong_message = PingPongMessage::Ping(PingPongAttribute::Cookie(129));
     let mut packet = NetlinkMessage::from(ping_pong_message);
@@ -195,8 +225,14 @@ fn main() {
     assert_eq!(deserialized_packet, packet);
 
     // This should print:
-    // NetlinkMessage { header: NetlinkHeader { length: 20, message_type: 18, flags: 0, sequence_number: 0, port_number: 0 }, payload: InnerMessage(Ping([0, 1, 2, 3])) }
+    // NetlinkMessage { header: NetlinkHeader { length: 24, message_type: 18, flags: 0, sequence_number: 0, port_number: 0 }, payload: InnerMessage(Ping(Cookie(129))) }
 
     // In case of structure, I think we only need to mess around with the payload structure because the structure of NetlinkHeader is going to be the same for every netlink message.
     println!("{:?}", packet);
+    if let NetlinkPayload::InnerMessage(message) = &packet.payload {
+        println!("{}", message);
+    }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,90 @@
+use netlink_packet_core::NetlinkMessage;
+use serialization_deserialization_netlink_rs::conntrack::{
+    AF_INET, NetfilterMessage, conntrack_dump_request,
+};
+use serialization_deserialization_netlink_rs::{roundtrip, route};
+
+use super::{PING_MESSAGE, PONG_MESSAGE, PingPongAttribute, PingPongMessage};
+
+#[test]
+fn test_display() {
+    let ping = PingPongMessage::Ping(PingPongAttribute::Cookie(129));
+    assert_eq!(ping.to_string(), "Ping cookie=129");
+
+    let pong = PingPongMessage::Pong(PingPongAttribute::Message("hello".to_string()));
+    assert_eq!(pong.to_string(), "Pong message=hello");
+}
+
+#[test]
+fn test_finalize_sets_message_type() {
+    let mut packet = NetlinkMessage::from(PingPongMessage::Ping(PingPongAttribute::Cookie(129)));
+    assert_eq!(packet.header.message_type, 0);
+    packet.finalize();
+    assert_eq!(packet.header.message_type, PING_MESSAGE);
+
+    let mut packet = NetlinkMessage::from(PingPongMessage::Pong(PingPongAttribute::Cookie(129)));
+    packet.finalize();
+    assert_eq!(packet.header.message_type, PONG_MESSAGE);
+}
+
+#[test]
+fn test_roundtrip() {
+    for message in [
+        PingPongMessage::Ping(PingPongAttribute::Cookie(129)),
+        PingPongMessage::Pong(PingPongAttribute::Message("hello".to_string())),
+    ] {
+        let mut packet = NetlinkMessage::from(message);
+        packet.finalize();
+        assert_eq!(roundtrip(&packet).unwrap(), packet);
+    }
+}
+
+#[test]
+fn test_cookie_ping_layout() {
+    let mut packet = NetlinkMessage::from(PingPongMessage::Ping(PingPongAttribute::Cookie(129)));
+    packet.finalize();
+    // The 16 bytes of nlmsghdr, then a single 8 bytes attribute.
+    assert_eq!(packet.header.length, 24);
+    assert_eq!(packet.buffer_len(), 24);
+
+    let mut buf = vec![0; packet.buffer_len()];
+    packet.serialize(&mut buf);
+    let cookie = 129u32.to_ne_bytes();
+    assert_eq!(
+        buf[16..],
+        [8, 0, 2, 0, cookie[0], cookie[1], cookie[2], cookie[3]]
+    );
+}
+
+#[test]
+fn test_route_by_message_type() {
+    #[derive(Debug, PartialEq)]
+    enum Routed {
+        Conntrack(NetlinkMessage<NetfilterMessage>),
+        PingPong(NetlinkMessage<PingPongMessage>),
+    }
+
+    fn dispatch(buf: &[u8]) -> Option<Routed> {
+        if let Some(message) = route::<NetfilterMessage>(buf).unwrap() {
+            return Some(Routed::Conntrack(message));
+        }
+        route::<PingPongMessage>(buf).unwrap().map(Routed::PingPong)
+    }
+
+    let mut ping = NetlinkMessage::from(PingPongMessage::Ping(PingPongAttribute::Cookie(129)));
+    ping.finalize();
+    let mut ping_raw = vec![0; ping.buffer_len()];
+    ping.serialize(&mut ping_raw);
+
+    let conntrack = conntrack_dump_request(AF_INET, 1);
+    let mut conntrack_raw = vec![0; conntrack.buffer_len()];
+    conntrack.serialize(&mut conntrack_raw);
+
+    assert_eq!(dispatch(&conntrack_raw), Some(Routed::Conntrack(conntrack)));
+    assert_eq!(dispatch(&ping_raw), Some(Routed::PingPong(ping)));
+
+    // NLMSG_DONE belongs to nobody.
+    let mut done = ping_raw.clone();
+    done[4..6].copy_from_slice(&3u16.to_ne_bytes());
+    assert_eq!(dispatch(&done), None);
+}
@@ -12,12 +12,16 @@
 //     1) `CAFFENE_CONTENT(u32)`
 //     2) `HOTNESS(u32)`
 //     3) `PERSON_NAME(String)`
+//     4) `CUP_LABEL(Vec<u8>)`
 
 use core::fmt;
 use netlink_packet_core::{
     DecodeError, Emitable, ErrorContext, NetlinkDeserializable, NetlinkHeader, NetlinkMessage,
     NetlinkPayload, NetlinkSerializable, Nla, NlaBuffer, NlasIterator, Parseable, buffer, emit_u32,
-    fields, getter, parse_string, parse_u32, setter,
+    fields, getter, parse_string, setter,
+};
+use serialization_deserialization_netlink_rs::{
+    NetlinkProtocol, StringPolicy, attributes_len, emit_header_and_nlas, read_int,
 };
 use std::mem::size_of;
 
@@ -32,12 +36,17 @@ pub const NLM_F_DRINK: u16 = 1 << 10;
 pub const NLM_F_WASH: u16 = 1 << 11;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
 pub enum BvgGenFamily {
-    Hot = 2,
-    Cold = 10,
+    Hot,
+    Cold,
+    // A family this code doesn't know about, kept as is so that the message
+    // can still be inspected and emitted again.
+    Unknown(u8),
 }
 
+const BVG_FAMILY_HOT: u8 = 2;
+const BVG_FAMILY_COLD: u8 = 10;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct BvgParseError {
     invalid_value: u8,
@@ -55,12 +64,31 @@ impl std::error::Error for BvgParseError {}
 impl TryFrom<u8> for BvgGenFamily {
     type Error = BvgParseError;
     fn try_from(v: u8) -> Result<Self, Self::Error> {
-        match v {
-            2 => Ok(BvgGenFamily::Hot),
-            10 => Ok(BvgGenFamily::Cold),
-            unknown_value => Err(BvgParseError {
+        match BvgGenFamily::from_lenient(v) {
+            BvgGenFamily::Unknown(unknown_value) => Err(BvgParseError {
                 invalid_value: unknown_value,
             }),
+            family => Ok(family),
+        }
+    }
+}
+impl BvgGenFamily {
+    // Same as `try_from()`, but unknown values become `Unknown` instead of
+    // an error.
+    pub fn from_lenient(v: u8) -> Self {
+        match v {
+            BVG_FAMILY_HOT => BvgGenFamily::Hot,
+            BVG_FAMILY_COLD => BvgGenFamily::Cold,
+            unknown_value => BvgGenFamily::Unknown(unknown_value),
+        }
+    }
+}
+impl From<BvgGenFamily> for u8 {
+    fn from(family: BvgGenFamily) -> u8 {
+        match family {
+            BvgGenFamily::Hot => BVG_FAMILY_HOT,
+            BvgGenFamily::Cold => BVG_FAMILY_COLD,
+            BvgGenFamily::Unknown(v) => v,
         }
     }
 }
@@ -92,17 +120,27 @@ impl Emitable for BvgGenMsg {
 
     fn emit(&self, buffer: &mut [u8]) {
         let mut buf = BvgGenMsgBuffer::new(buffer);
-        buf.set_family(self.family as u8);
+        buf.set_family(self.family.into());
         buf.set_version(self.version);
         buf.set_resource_id(self.resource_id);
     }
 }
 
+impl BvgGenMsg {
+    // `emit()` expects a buffer of at least `buffer_len()` bytes, like every
+    // `Emitable`. This one checks it first and returns an error instead.
+    pub fn emit_checked(&self, buffer: &mut [u8]) -> Result<(), DecodeError> {
+        BvgGenMsgBuffer::new_checked(&*buffer)?;
+        self.emit(buffer);
+        Ok(())
+    }
+}
+
 // Implement `Parseable` to define how to read `BvgGenMsg` from a byte buffer.
 impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<BvgGenMsgBuffer<&'a T>> for BvgGenMsg {
     fn parse(buf: &BvgGenMsgBuffer<&'a T>) -> Result<Self, DecodeError> {
         Ok(BvgGenMsg {
-            family: buf.family().try_into().unwrap(),
+            family: BvgGenFamily::from_lenient(buf.family()),
             version: buf.version(),
             resource_id: buf.resource_id(),
         })
@@ -113,12 +151,62 @@ impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<BvgGenMsgBuffer<&'a T>> for BvgGenMs
 const BVG_ATTR_CAFFEINE_CONTENT: u16 = 1;
 const BVG_ATTR_HOTNESS: u16 = 2;
 const BVG_ATTR_PERSON_NAME: u16 = 3;
+const BVG_ATTR_CUP_LABEL: u16 = 4;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BeverageAttribute {
     CaffeineContent(u32),
     Hotness(u32),
     PersonName(String),
+    // Whatever is written on the cup, kept as the raw bytes of the string
+    // without the null terminator. Nothing guarantees those are UTF-8, see
+    // `validate()`.
+    CupLabel(Vec<u8>),
+}
+
+// Returned instead of emitting an attribute the other end could not read
+// back as it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BvgEmitError {
+    InvalidUtf8 {
+        attribute: &'static str,
+        error: std::str::Utf8Error,
+    },
+    NullByte {
+        attribute: &'static str,
+    },
+}
+impl fmt::Display for BvgEmitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidUtf8 { attribute, error } => {
+                write!(f, "{} is not valid UTF-8: {}", attribute, error)
+            }
+            Self::NullByte { attribute } => write!(f, "{} contains a null byte", attribute),
+        }
+    }
+}
+impl std::error::Error for BvgEmitError {}
+
+impl BeverageAttribute {
+    // The string attributes have to be UTF-8 without any null byte in the
+    // middle, or the other end would cut them short. `String` already takes
+    // care of the first part, the raw ones have to be checked before being
+    // sent.
+    pub fn validate(&self) -> Result<(), BvgEmitError> {
+        let (attribute, bytes) = match self {
+            Self::PersonName(s) => ("PersonName", s.as_bytes()),
+            Self::CupLabel(v) => ("CupLabel", v.as_slice()),
+            Self::CaffeineContent(_) | Self::Hotness(_) => return Ok(()),
+        };
+        if let Err(error) = std::str::from_utf8(bytes) {
+            return Err(BvgEmitError::InvalidUtf8 { attribute, error });
+        }
+        if bytes.contains(&0) {
+            return Err(BvgEmitError::NullByte { attribute });
+        }
+        Ok(())
+    }
 }
 
 // for serializiation of NLAs
@@ -128,7 +216,8 @@ impl Nla for BeverageAttribute {
             Self::CaffeineContent(_) | Self::Hotness(_) => size_of::<u32>(),
             // Strings in netlink are typically null-terminated. We must
             // account for the extra byte.
-            Self::PersonName(s) => s.len() + 1,
+            Self::PersonName(s) => StringPolicy::NullTerminated.value_len(s),
+            Self::CupLabel(v) => v.len() + 1,
         }
     }
 
@@ -137,16 +226,17 @@ impl Nla for BeverageAttribute {
             Self::CaffeineContent(_) => BVG_ATTR_CAFFEINE_CONTENT,
             Self::Hotness(_) => BVG_ATTR_HOTNESS,
             Self::PersonName(_) => BVG_ATTR_PERSON_NAME,
+            Self::CupLabel(_) => BVG_ATTR_CUP_LABEL,
         }
     }
 
     fn emit_value(&self, buffer: &mut [u8]) {
         match self {
             Self::CaffeineContent(v) | Self::Hotness(v) => emit_u32(buffer, *v).unwrap(),
-            Self::PersonName(s) => {
-                buffer[..s.len()].copy_from_slice(s.as_bytes());
-                // Don't forget the null terminator
-                buffer[s.len()] = 0;
+            Self::PersonName(s) => StringPolicy::NullTerminated.emit(s, buffer),
+            Self::CupLabel(v) => {
+                buffer[..v.len()].copy_from_slice(v);
+                buffer[v.len()] = 0;
             }
         }
     }
@@ -159,17 +249,24 @@ impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for BeverageAttrib
 
         match buf.kind() {
             BVG_ATTR_CAFFEINE_CONTENT => {
-                let value = parse_u32(payload).context("invalid u32 for CaffeineContent")?;
+                let value = read_int!(u32, payload).context("invalid u32 for CaffeineContent")?;
                 Ok(Self::CaffeineContent(value))
             }
             BVG_ATTR_HOTNESS => {
-                let value = parse_u32(payload).context("invalid u32 for Hotness")?;
+                let value = read_int!(u32, payload).context("invalid u32 for Hotness")?;
                 Ok(Self::Hotness(value))
             }
             BVG_ATTR_PERSON_NAME => {
                 let value = parse_string(payload).context("invalid string for PersonName")?;
                 Ok(Self::PersonName(value))
             }
+            BVG_ATTR_CUP_LABEL => {
+                let len = payload
+                    .iter()
+                    .position(|b| *b == 0)
+                    .unwrap_or(payload.len());
+                Ok(Self::CupLabel(payload[..len].to_vec()))
+            }
             kind => Err(DecodeError::from(format!(
                 "Unknown NLA kind for BeverageAttribute: {}",
                 kind
@@ -191,6 +288,32 @@ pub enum BeverageMessage {
     },
 }
 
+impl BeverageMessage {
+    pub fn nlas(&self) -> &[BeverageAttribute] {
+        match self {
+            Self::Tea { nlas, .. } | Self::Coffee { nlas, .. } => nlas,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), BvgEmitError> {
+        self.nlas().iter().try_for_each(BeverageAttribute::validate)
+    }
+}
+
+// `serialize()` can't fail, so this checks every attribute of `packet` first,
+// then finalizes it and returns its bytes.
+pub fn serialize_checked(
+    packet: &mut NetlinkMessage<BeverageMessage>,
+) -> Result<Vec<u8>, BvgEmitError> {
+    if let NetlinkPayload::InnerMessage(message) = &packet.payload {
+        message.validate()?;
+    }
+    packet.finalize();
+    let mut buf = vec![0; packet.buffer_len()];
+    packet.serialize(&mut buf);
+    Ok(buf)
+}
+
 // for serializing
 impl NetlinkSerializable for BeverageMessage {
     fn message_type(&self) -> u16 {
@@ -202,8 +325,8 @@ impl NetlinkSerializable for BeverageMessage {
 
     fn buffer_len(&self) -> usize {
         let nlas_len = match self {
-            Self::Tea { nlas, .. } => nlas.as_slice().buffer_len(),
-            Self::Coffee { nlas, .. } => nlas.as_slice().buffer_len(),
+            Self::Tea { nlas, .. } => attributes_len(nlas),
+            Self::Coffee { nlas, .. } => attributes_len(nlas),
         };
         BVG_GEN_MSG_LEN + nlas_len
     }
@@ -214,12 +337,10 @@ impl NetlinkSerializable for BeverageMessage {
             Self::Coffee { header, nlas } => (header, nlas),
         };
 
-        // First, emit the fixed-size protocol header.
-        header.emit(&mut buffer[..BVG_GEN_MSG_LEN]);
-
-        // Then, emit all the NLAs right after it. The `Emitable` impl for
-        // `&[T: Nla]` handles iterating and writing them correctly.
-        nlas.as_slice().emit(&mut buffer[BVG_GEN_MSG_LEN..]);
+        // Emit the fixed-size protocol header, then all the NLAs right after
+        // it. The `Emitable` impl for `&[T: Nla]` handles iterating and
+        // writing them correctly.
+        emit_header_and_nlas(header, nlas, buffer);
     }
 }
 
@@ -269,6 +390,33 @@ impl NetlinkDeserializable for BeverageMessage {
     }
 }
 
+// Human readable form, e.g. `hotness=95` or `person=Alice`.
+impl fmt::Display for BeverageAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CaffeineContent(v) => write!(f, "caffeine={}", v),
+            Self::Hotness(v) => write!(f, "hotness={}", v),
+            Self::PersonName(s) => write!(f, "person={}", s),
+            Self::CupLabel(v) => write!(f, "label={}", String::from_utf8_lossy(v)),
+        }
+    }
+}
+
+// Human readable form, e.g. `Tea[Hot] hotness=95 person=Alice caffeine=30`.
+impl fmt::Display for BeverageMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, header, nlas) = match self {
+            Self::Tea { header, nlas } => ("Tea", header, nlas),
+            Self::Coffee { header, nlas } => ("Coffee", header, nlas),
+        };
+        write!(f, "{}[{:?}]", name, header.family)?;
+        for nla in nlas {
+            write!(f, " {}", nla)?;
+        }
+        Ok(())
+    }
+}
+
 // to do stuff like `NetlinkMessage::from(my_beverage_message)`.
 impl From<BeverageMessage> for NetlinkPayload<BeverageMessage> {
     fn from(message: BeverageMessage) -> Self {
@@ -276,6 +424,18 @@ impl From<BeverageMessage> for NetlinkPayload<BeverageMessage> {
     }
 }
 
+// Note that `COFFEE_MESSAGE_TYPE` is also the pingpong `PONG_MESSAGE`, so the
+// two protocols can't share a reader.
+impl NetlinkProtocol for BeverageMessage {
+    fn owns_message_type(message_type: u16) -> bool {
+        matches!(message_type, TEA_MESSAGE_TYPE | COFFEE_MESSAGE_TYPE)
+    }
+
+    fn deserialize(buf: &[u8]) -> Result<NetlinkMessage<Self>, DecodeError> {
+        NetlinkMessage::deserialize(buf)
+    }
+}
+
 fn main() {
     let tea_request = BeverageMessage::Tea {
         header: BvgGenMsg {
@@ -295,15 +455,12 @@ fn main() {
     packet.header.flags = netlink_packet_core::NLM_F_REQUEST | NLM_F_SERVE | NLM_F_DRINK;
     packet.header.sequence_number = 1;
 
-    // `finalize()` calculates the total packet length and sets the message type
-    // in the header based on our `NetlinkSerializable` implementation.
-    packet.finalize();
-
-    println!("Original Packet: {:#?}", packet);
+    println!("Original Packet: {}", tea_request);
 
-    // Serialize the packet into a byte buffer.
-    let mut buf = vec![0; packet.buffer_len()];
-    packet.serialize(&mut buf);
+    // Serialize the packet into a byte buffer. `finalize()`, called on the
+    // way, calculates the total packet length and sets the message type in
+    // the header based on our `NetlinkSerializable` implementation.
+    let buf = serialize_checked(&mut packet).unwrap();
 
     println!("\nSerialized Bytes: {:?}", buf);
 
@@ -312,3 +469,5 @@ fn main() {
     // Verify that the round trip was successful.
     assert_eq!(packet, deserialized_packet);
 }
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,193 @@
+use netlink_packet_core::{Emitable, NLM_F_REQUEST, NetlinkMessage, NlaBuffer, Parseable};
+use serialization_deserialization_netlink_rs::roundtrip;
+
+use crate::{
+    BeverageAttribute, BeverageMessage, BvgEmitError, BvgGenFamily, BvgGenMsg, BvgGenMsgBuffer,
+    COFFEE_MESSAGE_TYPE, NLM_F_DRINK, NLM_F_SERVE, TEA_MESSAGE_TYPE, serialize_checked,
+};
+
+#[test]
+fn test_bvg_gen_msg_emit_too_small() {
+    let header = BvgGenMsg {
+        family: BvgGenFamily::Hot,
+        version: 1,
+        resource_id: 101,
+    };
+
+    let mut buf = [0u8; 3];
+    assert!(header.emit_checked(&mut buf).is_err());
+    // Nothing must have been written into the short buffer.
+    assert_eq!(buf, [0, 0, 0]);
+
+    let mut buf = [0u8; 4];
+    header.emit_checked(&mut buf).unwrap();
+    let resource_id = 101u16.to_ne_bytes();
+    assert_eq!(buf, [2, 1, resource_id[0], resource_id[1]]);
+}
+
+#[test]
+fn test_display() {
+    let tea = BeverageMessage::Tea {
+        header: BvgGenMsg {
+            family: BvgGenFamily::Hot,
+            version: 1,
+            resource_id: 101,
+        },
+        nlas: vec![
+            BeverageAttribute::Hotness(95),
+            BeverageAttribute::PersonName("Alice".to_string()),
+            BeverageAttribute::CaffeineContent(30),
+        ],
+    };
+    assert_eq!(
+        tea.to_string(),
+        "Tea[Hot] hotness=95 person=Alice caffeine=30"
+    );
+
+    let coffee = BeverageMessage::Coffee {
+        header: BvgGenMsg {
+            family: BvgGenFamily::Cold,
+            version: 1,
+            resource_id: 7,
+        },
+        nlas: vec![],
+    };
+    assert_eq!(coffee.to_string(), "Coffee[Cold]");
+}
+
+#[test]
+fn test_finalize_sets_message_type() {
+    let header = BvgGenMsg {
+        family: BvgGenFamily::Hot,
+        version: 1,
+        resource_id: 101,
+    };
+
+    let mut packet = NetlinkMessage::from(BeverageMessage::Tea {
+        header,
+        nlas: vec![],
+    });
+    assert_eq!(packet.header.message_type, 0);
+    packet.finalize();
+    assert_eq!(packet.header.message_type, TEA_MESSAGE_TYPE);
+
+    let mut packet = NetlinkMessage::from(BeverageMessage::Coffee {
+        header,
+        nlas: vec![],
+    });
+    packet.finalize();
+    assert_eq!(packet.header.message_type, COFFEE_MESSAGE_TYPE);
+}
+
+#[test]
+fn test_unknown_family_roundtrip() {
+    assert!(BvgGenFamily::try_from(99).is_err());
+    assert_eq!(BvgGenFamily::try_from(10), Ok(BvgGenFamily::Cold));
+
+    let raw = [99, 1, 0, 0];
+    let header = BvgGenMsg::parse(&BvgGenMsgBuffer::new(&raw)).unwrap();
+    assert_eq!(header.family, BvgGenFamily::Unknown(99));
+
+    let mut buf = [0; 4];
+    header.emit(&mut buf);
+    assert_eq!(buf, raw);
+}
+
+#[test]
+fn test_roundtrip() {
+    let mut packet = NetlinkMessage::from(BeverageMessage::Tea {
+        header: BvgGenMsg {
+            family: BvgGenFamily::Hot,
+            version: 1,
+            resource_id: 101,
+        },
+        nlas: vec![
+            BeverageAttribute::Hotness(95),
+            BeverageAttribute::PersonName("Alice".to_string()),
+            BeverageAttribute::CaffeineContent(30),
+        ],
+    });
+    packet.header.flags = NLM_F_REQUEST | NLM_F_SERVE | NLM_F_DRINK;
+    packet.header.sequence_number = 1;
+    packet.finalize();
+    assert_eq!(roundtrip(&packet).unwrap(), packet);
+}
+
+#[test]
+fn test_validate_string_attributes() {
+    let mut tea = BeverageMessage::Tea {
+        header: BvgGenMsg {
+            family: BvgGenFamily::Hot,
+            version: 1,
+            resource_id: 101,
+        },
+        nlas: vec![
+            BeverageAttribute::PersonName("Alice".to_string()),
+            BeverageAttribute::CupLabel(b"Earl Grey".to_vec()),
+        ],
+    };
+    let mut packet = NetlinkMessage::from(tea.clone());
+    let buf = serialize_checked(&mut packet).unwrap();
+    assert_eq!(
+        NetlinkMessage::<BeverageMessage>::deserialize(&buf).unwrap(),
+        packet
+    );
+
+    // Latin-1 "café".
+    if let BeverageMessage::Tea { nlas, .. } = &mut tea {
+        nlas[1] = BeverageAttribute::CupLabel(b"caf\xe9".to_vec());
+    }
+    let error = serialize_checked(&mut NetlinkMessage::from(tea)).unwrap_err();
+    assert!(
+        matches!(
+            error,
+            BvgEmitError::InvalidUtf8 {
+                attribute: "CupLabel",
+                ..
+            }
+        ),
+        "{}",
+        error
+    );
+
+    assert_eq!(
+        BeverageAttribute::CupLabel(b"Earl\0Grey".to_vec()).validate(),
+        Err(BvgEmitError::NullByte {
+            attribute: "CupLabel"
+        })
+    );
+    assert_eq!(
+        BeverageAttribute::PersonName("Al\0ice".to_string()).validate(),
+        Err(BvgEmitError::NullByte {
+            attribute: "PersonName"
+        })
+    );
+}
+
+#[test]
+fn test_attribute_length_below_header() {
+    // A Tea whose only attribute claims to be 2 bytes long, less than its
+    // own header.
+    #[rustfmt::skip]
+    let raw = [
+        0x1c, 0x00, 0x00, 0x00, 0x13, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x02, 0x01, 0x65, 0x00,
+        0x02, 0x00, 0x02, 0x00, 0x5f, 0x00, 0x00, 0x00,
+    ];
+    let error = NetlinkMessage::<BeverageMessage>::deserialize(&raw)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("beverage attributes"), "{}", error);
+}
+
+#[test]
+fn test_integer_attribute_wrong_length() {
+    // Hotness is a u32, this one only has 2 bytes.
+    let raw = [0x06, 0x00, 0x02, 0x00, 0x5f, 0x00, 0x00, 0x00];
+    let error = BeverageAttribute::parse(&NlaBuffer::new_checked(&raw[..]).unwrap())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("invalid u32 for Hotness"), "{}", error);
+    assert!(error.contains("expected 4 bytes, got 2"), "{}", error);
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use netlink_packet_core::{NLM_F_DUMP, NLM_F_REQUEST, NetlinkMessage};
+
+use crate::conntrack::{
+    ConntrackAttribute, Direction, Filter, FiveTuple, IPPROTO_ICMP, IPPROTO_ICMPV6, IPTuple,
+    NetfilterMessage, Nfgenmsg, ProtoTuple, Tuple,
+};
+
+// Returned when the builder is asked for a request the kernel would not
+// understand the way the caller expects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildError(&'static str);
+
+impl std::error::Error for BuildError {}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+const NO_REPLY_TUPLE: BuildError = BuildError("a reply tuple needs both addresses and a protocol");
+
+// Builds conntrack requests without having to nest the attribute enums by
+// hand. The address and port setters fill in the original tuple.
+#[derive(Debug, Default)]
+pub struct ConntrackRequestBuilder {
+    family: u8,
+    dump: bool,
+    sequence_number: u32,
+    src: Option<IpAddr>,
+    dst: Option<IpAddr>,
+    protocol: Option<u8>,
+    src_port: Option<u16>,
+    dst_port: Option<u16>,
+    reply_from_orig: bool,
+    filter: Vec<Filter>,
+    nlas: Vec<ConntrackAttribute>,
+}
+
+impl ConntrackRequestBuilder {
+    pub fn new(family: u8) -> Self {
+        ConntrackRequestBuilder {
+            family,
+            ..Default::default()
+        }
+    }
+
+    // Ask for every matching entry (NLM_F_DUMP) instead of a single one.
+    pub fn dump(mut self) -> Self {
+        self.dump = true;
+        self
+    }
+
+    pub fn sequence_number(mut self, sequence_number: u32) -> Self {
+        self.sequence_number = sequence_number;
+        self
+    }
+
+    pub fn src(mut self, addr: IpAddr) -> Self {
+        self.src = Some(addr);
+        self
+    }
+
+    pub fn dst(mut self, addr: IpAddr) -> Self {
+        self.dst = Some(addr);
+        self
+    }
+
+    // Not calling this, or passing 0 (IPPROTO_IP), leaves CTA_TUPLE_PROTO out
+    // of the tuple, which the kernel takes as any protocol when filtering a
+    // dump. Ports can't be matched without a protocol.
+    pub fn protocol(mut self, protocol: u8) -> Self {
+        self.protocol = Some(protocol).filter(|protocol| *protocol != 0);
+        self
+    }
+
+    // Ports only exist for port-based protocols: asking for one on an ICMP
+    // tuple, usually by copying TCP code, fails the build.
+    pub fn src_port(mut self, port: u16) -> Self {
+        self.src_port = Some(port);
+        self
+    }
+
+    pub fn dst_port(mut self, port: u16) -> Self {
+        self.dst_port = Some(port);
+        self
+    }
+
+    // Also send the reply tuple, derived from the original one as if the
+    // connection were not NATed. The original tuple must then be complete:
+    // both addresses and the protocol.
+    pub fn reply_from_orig(mut self) -> Self {
+        self.reply_from_orig = true;
+        self
+    }
+
+    // Only dump the entries matching the fields selected by `flags`
+    // (`CTA_FILTER_FLAG_*`) of the `direction` tuple sent along. The setters
+    // above fill in the original one, a reply one can be passed to
+    // `attribute()`. Requires Linux 5.8 or later.
+    pub fn filter(mut self, direction: Direction, flags: u32) -> Self {
+        self.filter.push(match direction {
+            Direction::Original => Filter::OrigFlags(flags),
+            Direction::Reply => Filter::ReplyFlags(flags),
+        });
+        self
+    }
+
+    // Append any other attribute to the request as is.
+    pub fn attribute(mut self, nla: ConntrackAttribute) -> Self {
+        self.nlas.push(nla);
+        self
+    }
+
+    fn tuple_orig(&self) -> Result<Option<ConntrackAttribute>, BuildError> {
+        if self.protocol.is_none() && (self.src_port.is_some() || self.dst_port.is_some()) {
+            return Err(BuildError("ports need a protocol"));
+        }
+        if matches!(self.protocol, Some(IPPROTO_ICMP | IPPROTO_ICMPV6))
+            && (self.src_port.is_some() || self.dst_port.is_some())
+        {
+            return Err(BuildError("ICMP tuples have no ports"));
+        }
+        let ip: Vec<IPTuple> = [
+            self.src.map(IPTuple::SourceAddress),
+            self.dst.map(IPTuple::DestinationAddress),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let proto: Vec<ProtoTuple> = [
+            self.protocol.map(ProtoTuple::Protocol),
+            self.src_port.map(ProtoTuple::SourcePort),
+            self.dst_port.map(ProtoTuple::DestinationPort),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        let mut tuples = Vec::new();
+        if !ip.is_empty() {
+            tuples.push(Tuple::Ip(ip));
+        }
+        if !proto.is_empty() {
+            tuples.push(Tuple::Proto(proto));
+        }
+        Ok((!tuples.is_empty()).then_some(ConntrackAttribute::CtaTupleOrig(tuples)))
+    }
+
+    // Build a finalized IPCTNL_MSG_CT_GET. The kernel treats a GET without a
+    // tuple as a dump, so that is only allowed when `dump()` was requested.
+    pub fn build_get(self) -> Result<NetlinkMessage<NetfilterMessage>, BuildError> {
+        let mut nlas = Vec::new();
+        match self.tuple_orig()? {
+            Some(ConntrackAttribute::CtaTupleOrig(tuples)) if self.reply_from_orig => {
+                let reply = FiveTuple::from_tuples(&tuples)
+                    .ok_or(NO_REPLY_TUPLE)?
+                    .reply();
+                nlas.push(ConntrackAttribute::CtaTupleOrig(tuples));
+                nlas.push(ConntrackAttribute::CtaTupleReply(reply.to_tuples()));
+            }
+            Some(tuple) => nlas.push(tuple),
+            None if self.reply_from_orig => return Err(NO_REPLY_TUPLE),
+            None if !self.dump => {
+                return Err(BuildError(
+                    "a GET for a single entry needs an original tuple",
+                ));
+            }
+            None => {}
+        }
+        if !self.filter.is_empty() {
+            if !self.dump {
+                return Err(BuildError("CTA_FILTER only applies to dumps"));
+            }
+            nlas.push(ConntrackAttribute::CtaFilter(self.filter));
+        }
+        nlas.extend(self.nlas);
+
+        let mut packet = NetlinkMessage::from(NetfilterMessage::ConntrackGet {
+            header: Nfgenmsg {
+                nfgen_family: self.family,
+                version: 0,
+                resource_id: 0,
+            },
+            nlas,
+        });
+        packet.header.flags = NLM_F_REQUEST;
+        if self.dump {
+            packet.header.flags |= NLM_F_DUMP;
+        }
+        packet.header.sequence_number = self.sequence_number;
+        packet.finalize();
+        Ok(packet)
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use netlink_packet_core::{
+    DecodeError, NetlinkBuffer, NetlinkMessage, NetlinkPayload, NlasIterator,
+};
+
+use crate::conntrack::{CTA_ID, NFGENMSG_LEN, NetfilterMessage};
+
+// Remembers the entries of previous dumps, keyed by CTA_ID, so that polling a
+// large table only decodes the entries that changed since the last time.
+// Entries are never evicted, call `clear()` once in a while if connections
+// come and go.
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    entries: HashMap<u32, Cached>,
+    // Number of `parse()` calls served from the cache.
+    pub hits: usize,
+    pub misses: usize,
+}
+
+// The bytes an entry was decoded from, after the nlmsghdr: its sequence
+// number and port id change with every dump while the entry itself doesn't.
+#[derive(Debug)]
+struct Cached {
+    message_type: u16,
+    payload: Vec<u8>,
+    message: NetfilterMessage,
+}
+
+impl ParseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Decode the single netlink message in `raw`, reusing the previous result
+    // if an entry with the same CTA_ID had the same message type and payload.
+    // The rest of the nlmsghdr is not compared, so entries of a dump answering
+    // another request still hit. Only entries carrying a CTA_ID, as dumped by
+    // the kernel, can be cached.
+    pub fn parse(&mut self, raw: &[u8]) -> Result<&NetfilterMessage, DecodeError> {
+        let buffer = NetlinkBuffer::new_checked(raw)?;
+        let message_type = buffer.message_type();
+        let payload = buffer.payload();
+        let id = find_id(payload)?.ok_or_else(|| DecodeError::from("entry has no CTA_ID"))?;
+
+        match self.entries.entry(id) {
+            Entry::Occupied(cached)
+                if cached.get().message_type == message_type && cached.get().payload == payload =>
+            {
+                self.hits += 1;
+                Ok(&cached.into_mut().message)
+            }
+            entry => {
+                self.misses += 1;
+                let NetlinkPayload::InnerMessage(message) =
+                    NetlinkMessage::<NetfilterMessage>::deserialize(raw)?.payload
+                else {
+                    return Err(DecodeError::from("not a conntrack entry"));
+                };
+                let value = Cached {
+                    message_type,
+                    payload: payload.to_vec(),
+                    message,
+                };
+                let slot = match entry {
+                    Entry::Occupied(mut cached) => {
+                        cached.insert(value);
+                        cached.into_mut()
+                    }
+                    Entry::Vacant(vacant) => vacant.insert(value),
+                };
+                Ok(&slot.message)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+// Look for the top level CTA_ID in the payload of a message without decoding
+// anything else.
+fn find_id(payload: &[u8]) -> Result<Option<u32>, DecodeError> {
+    let Some(nlas) = payload.get(NFGENMSG_LEN..) else {
+        return Err(DecodeError::from(
+            "Payload is too short for NFGENMSG header",
+        ));
+    };
+    for nla in NlasIterator::new(nlas) {
+        let nla = nla?;
+        if nla.kind() == CTA_ID {
+            return read_int!(be u32, nla.value()).map(Some);
+        }
+    }
+    Ok(None)
+}
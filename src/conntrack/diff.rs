@@ -0,0 +1,40 @@
+use netlink_packet_core::Nla;
+
+use crate::conntrack::{ConntrackAttribute, NetfilterMessage};
+
+// A single difference between two observations of the same connection.
+#[derive(PartialEq, Debug)]
+pub enum AttrChange<'a> {
+    Added(&'a ConntrackAttribute),
+    Removed(&'a ConntrackAttribute),
+    Changed {
+        old: &'a ConntrackAttribute,
+        new: &'a ConntrackAttribute,
+    },
+}
+
+// Compare the attributes of two entries, matching them up by kind. Changes
+// are reported in the order the attributes appear in `old`, followed by the
+// attributes only present in `new`.
+pub fn diff<'a>(old: &'a NetfilterMessage, new: &'a NetfilterMessage) -> Vec<AttrChange<'a>> {
+    let mut changes = Vec::new();
+
+    for old_nla in old.nlas() {
+        match new.nlas().iter().find(|nla| nla.kind() == old_nla.kind()) {
+            Some(new_nla) if new_nla != old_nla => changes.push(AttrChange::Changed {
+                old: old_nla,
+                new: new_nla,
+            }),
+            Some(_) => {}
+            None => changes.push(AttrChange::Removed(old_nla)),
+        }
+    }
+
+    for new_nla in new.nlas() {
+        if !old.nlas().iter().any(|nla| nla.kind() == new_nla.kind()) {
+            changes.push(AttrChange::Added(new_nla));
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+
+use crate::conntrack::{
+    ConntrackAttribute, FiveTuple, NetfilterMessage, Nfgenmsg, ProtoInfo, Tuple,
+};
+
+// A flat view of a conntrack entry, for callers who would rather not walk the
+// attribute enums. Each field is `None` when the attribute is absent.
+#[derive(PartialEq, Debug, Default, Clone)]
+pub struct ConntrackEntry {
+    pub orig_tuple: Option<Vec<Tuple>>,
+    pub reply_tuple: Option<Vec<Tuple>>,
+    pub status: Option<u32>,
+    pub mark: Option<u32>,
+    // Seconds left before the entry expires.
+    pub timeout: Option<u32>,
+    pub id: Option<u32>,
+    pub protoinfo: Option<Vec<ProtoInfo>>,
+}
+
+impl ConntrackEntry {
+    // Attributes without a field here (labels, mark mask...) are dropped, as
+    // is the nfgenmsg header.
+    pub fn from_message(message: NetfilterMessage) -> Self {
+        let nlas = match message {
+            NetfilterMessage::ConntrackGet { nlas, .. }
+            | NetfilterMessage::ConntrackNew { nlas, .. }
+            | NetfilterMessage::ConntrackDelete { nlas, .. } => nlas,
+            NetfilterMessage::Raw { .. } => Vec::new(),
+        };
+
+        let mut entry = ConntrackEntry::default();
+        for nla in nlas {
+            entry.apply(nla);
+        }
+        entry
+    }
+
+    // Overlay the attributes of an update event on this entry. The kernel
+    // often only sends what changed (e.g. the status once the entry gets
+    // assured), every field the update leaves out is kept as is.
+    pub fn apply_update(&mut self, update: &NetfilterMessage) {
+        for nla in update.nlas() {
+            self.apply(nla.clone());
+        }
+    }
+
+    fn apply(&mut self, nla: ConntrackAttribute) {
+        match nla {
+            ConntrackAttribute::CtaTupleOrig(tuples) => self.orig_tuple = Some(tuples),
+            ConntrackAttribute::CtaTupleReply(tuples) => self.reply_tuple = Some(tuples),
+            ConntrackAttribute::CtaStatus(v) => self.status = Some(v),
+            ConntrackAttribute::CtaMark(v) => self.mark = Some(v),
+            ConntrackAttribute::CtaTimeout(v) => self.timeout = Some(v),
+            ConntrackAttribute::CtaId(v) => self.id = Some(v),
+            ConntrackAttribute::CtaProtoInfo(infos) => self.protoinfo = Some(infos),
+            _ => {}
+        }
+    }
+
+    // Build the `IPCTNL_MSG_CT_NEW` message describing this entry. Attributes
+    // are laid out in the order the kernel dumps them.
+    pub fn into_message(self, header: Nfgenmsg) -> NetfilterMessage {
+        let nlas = [
+            self.orig_tuple.map(ConntrackAttribute::CtaTupleOrig),
+            self.reply_tuple.map(ConntrackAttribute::CtaTupleReply),
+            self.status.map(ConntrackAttribute::CtaStatus),
+            self.mark.map(ConntrackAttribute::CtaMark),
+            self.id.map(ConntrackAttribute::CtaId),
+            self.timeout.map(ConntrackAttribute::CtaTimeout),
+            self.protoinfo.map(ConntrackAttribute::CtaProtoInfo),
+        ];
+        NetfilterMessage::ConntrackNew {
+            header,
+            nlas: nlas.into_iter().flatten().collect(),
+        }
+    }
+}
+
+// e.g. `src=10.0.0.1 dst=10.0.0.2 sport=1234 dport=80 ... timeout 4d 23h mark=0`.
+// Fields that are absent are skipped.
+impl fmt::Display for ConntrackEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tuples = [&self.orig_tuple, &self.reply_tuple]
+            .into_iter()
+            .filter_map(|tuples| FiveTuple::from_tuples(tuples.as_deref()?));
+        let fields = tuples
+            .map(|tuple| tuple.to_string())
+            .chain(
+                self.timeout
+                    .map(|t| format!("timeout {}", format_timeout(t))),
+            )
+            .chain(self.mark.map(|mark| format!("mark={}", mark)))
+            .chain(self.id.map(|id| format!("id={}", id)));
+        for (i, field) in fields.enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(&field)?;
+        }
+        Ok(())
+    }
+}
+
+// Render a number of seconds with its two most significant units, e.g.
+// `4d 23h` for 431999 or `45s` for 45. Good enough to see at a glance how long
+// an entry has left.
+pub fn format_timeout(secs: u32) -> String {
+    let units = [(86400, "d"), (3600, "h"), (60, "m"), (1, "s")];
+    let Some(first) = units.iter().position(|(len, _)| secs >= *len) else {
+        return "0s".to_string();
+    };
+
+    let mut left = secs;
+    let mut parts = Vec::new();
+    for (len, name) in units.iter().skip(first).take(2) {
+        parts.push(format!("{}{}", left / len, name));
+        left %= len;
+    }
+    parts.join(" ")
+}
@@ -0,0 +1,82 @@
+use netlink_packet_core::{NLM_F_CREATE, NLM_F_EXCL, NetlinkMessage, NetlinkPayload};
+
+use crate::conntrack::{Direction, NetfilterMessage, protocol_name, tcp_conntrack_state_name};
+
+// Format an event received on the conntrack multicast groups the way
+// `conntrack -E` does, e.g.
+// `[NEW] tcp 6 120 SYN_SENT src=... dst=... sport=... dport=... [UNREPLIED] src=...`.
+// The kernel's IPCTNL_MSG_CT_NEW messages are NEW events when they have
+// `NLM_F_CREATE` set and UPDATE ones otherwise, its IPCTNL_MSG_CT_DELETE
+// messages are DESTROY events. Returns `None` for anything else.
+pub fn format_event(message: &NetlinkMessage<NetfilterMessage>) -> Option<String> {
+    let NetlinkPayload::InnerMessage(entry) = &message.payload else {
+        return None;
+    };
+    let event = match entry {
+        NetfilterMessage::ConntrackNew { .. }
+            if message.header.flags & (NLM_F_CREATE | NLM_F_EXCL) != 0 =>
+        {
+            "NEW"
+        }
+        NetfilterMessage::ConntrackNew { .. } => "UPDATE",
+        NetfilterMessage::ConntrackDelete { .. } => "DESTROY",
+        _ => return None,
+    };
+
+    let orig = entry.tuple(Direction::Original)?;
+    let status = entry.status();
+
+    let protocol = protocol_name(orig.protocol).unwrap_or("unknown");
+    let mut line = format!("[{}] {} {}", event, protocol, orig.protocol);
+    if let Some(timeout) = entry.timeout() {
+        line += &format!(" {}", timeout);
+    }
+    if let Some(state) = entry.tcp_state() {
+        line += &format!(" {}", tcp_conntrack_state_name(state));
+    }
+    line += &format!(" {}", orig);
+    if status.is_some_and(|status| !status.is_seen_reply()) {
+        line += " [UNREPLIED]";
+    }
+    if let Some(reply) = entry.tuple(Direction::Reply) {
+        line += &format!(" {}", reply);
+    }
+    if status.is_some_and(|status| status.is_assured()) {
+        line += " [ASSURED]";
+    }
+    if let Some(mark) = entry.mark() {
+        line += &format!(" mark={}", mark);
+    }
+    Some(line)
+}
+
+// Why the kernel sent a DELETE event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteReason {
+    // The entry expired, or was dropped by the kernel for some other reason
+    // of its own (e.g. a TCP reset). These all look the same on the wire.
+    Timeout,
+    // A netlink user deleted it, e.g. `conntrack -D`.
+    Destroy,
+    // Not a DELETE event, or one without the IPS_DYING status bit.
+    Unknown,
+}
+
+// The kernel does not say why an entry went away, but it sends the event with
+// the port id of whoever asked for the deletion. That is 0 when the kernel
+// removed the entry on its own.
+pub fn delete_reason(message: &NetlinkMessage<NetfilterMessage>) -> DeleteReason {
+    let NetlinkPayload::InnerMessage(entry @ NetfilterMessage::ConntrackDelete { .. }) =
+        &message.payload
+    else {
+        return DeleteReason::Unknown;
+    };
+    if entry.status().is_none_or(|status| !status.is_dying()) {
+        return DeleteReason::Unknown;
+    }
+    if message.header.port_number == 0 {
+        DeleteReason::Timeout
+    } else {
+        DeleteReason::Destroy
+    }
+}
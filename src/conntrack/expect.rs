@@ -0,0 +1,167 @@
+use crate::{attributes_len, emit_header_and_nlas};
+use netlink_packet_core::{
+    DecodeError, Emitable, ErrorContext, NetlinkDeserializable, NetlinkHeader, NetlinkPayload,
+    NetlinkSerializable, Nla, NlaBuffer, Parseable, emit_u32_be,
+};
+
+use crate::conntrack::{NFGENMSG_LEN, Nfgenmsg, NfgenmsgBuffer, Tuple, parse_nlas};
+
+// Expectations live in their own nfnetlink subsystem.
+pub const NFNL_SUBSYS_CTNETLINK_EXP: u16 = 2;
+pub const IPCTNL_MSG_EXP_NEW: u16 = 0;
+pub const IPCTNL_MSG_EXP_GET: u16 = 1;
+pub const NETFILTER_EXPECT_NEW_MESSAGE_TYPE: u16 =
+    NFNL_SUBSYS_CTNETLINK_EXP << 8 | IPCTNL_MSG_EXP_NEW;
+pub const NETFILTER_EXPECT_GET_MESSAGE_TYPE: u16 =
+    NFNL_SUBSYS_CTNETLINK_EXP << 8 | IPCTNL_MSG_EXP_GET;
+
+// A connection a helper (ftp, sip...) expects to see, e.g. the data channel
+// announced on an FTP control connection.
+#[derive(PartialEq, Debug)]
+pub enum ExpectMessage {
+    ExpectGet {
+        header: Nfgenmsg,
+        nlas: Vec<ExpectAttribute>,
+    },
+    ExpectNew {
+        header: Nfgenmsg,
+        nlas: Vec<ExpectAttribute>,
+    },
+}
+
+impl NetlinkSerializable for ExpectMessage {
+    fn message_type(&self) -> u16 {
+        match self {
+            Self::ExpectGet { .. } => NETFILTER_EXPECT_GET_MESSAGE_TYPE,
+            Self::ExpectNew { .. } => NETFILTER_EXPECT_NEW_MESSAGE_TYPE,
+        }
+    }
+
+    fn buffer_len(&self) -> usize {
+        match self {
+            Self::ExpectGet { header, nlas } | Self::ExpectNew { header, nlas } => {
+                header.buffer_len() + attributes_len(nlas)
+            }
+        }
+    }
+
+    fn serialize(&self, buffer: &mut [u8]) {
+        match self {
+            Self::ExpectGet { header, nlas } | Self::ExpectNew { header, nlas } => {
+                emit_header_and_nlas(header, nlas, buffer)
+            }
+        }
+    }
+}
+
+impl NetlinkDeserializable for ExpectMessage {
+    type Error = DecodeError;
+
+    fn deserialize(header: &NetlinkHeader, payload: &[u8]) -> Result<Self, Self::Error> {
+        if payload.len() < NFGENMSG_LEN {
+            return Err(DecodeError::from(
+                "Payload is too short for NFGENMSG header",
+            ));
+        }
+
+        let nfgen_msg_buf = NfgenmsgBuffer::new(&payload[..NFGENMSG_LEN]);
+        let nfgen_header = Nfgenmsg::parse(&nfgen_msg_buf)?;
+        let nlas = parse_nlas(
+            &payload[NFGENMSG_LEN..],
+            "failed to parse expectation attributes",
+        )?;
+
+        match header.message_type {
+            NETFILTER_EXPECT_GET_MESSAGE_TYPE => Ok(Self::ExpectGet {
+                header: nfgen_header,
+                nlas,
+            }),
+            NETFILTER_EXPECT_NEW_MESSAGE_TYPE => Ok(Self::ExpectNew {
+                header: nfgen_header,
+                nlas,
+            }),
+            _ => Err(DecodeError::from(format!(
+                "Unknown message type for conntrack expectations: {}",
+                header.message_type
+            ))),
+        }
+    }
+}
+
+impl From<ExpectMessage> for NetlinkPayload<ExpectMessage> {
+    fn from(message: ExpectMessage) -> Self {
+        NetlinkPayload::InnerMessage(message)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum ExpectAttribute {
+    // The original tuple of the connection that created the expectation.
+    Master(Vec<Tuple>),
+    // The tuple of the expected connection, and which of its fields must
+    // match.
+    Tuple(Vec<Tuple>),
+    Mask(Vec<Tuple>),
+    // Seconds before the expectation is dropped.
+    Timeout(u32),
+    Id(u32),
+}
+pub const CTA_EXPECT_MASTER: u16 = 1;
+pub const CTA_EXPECT_TUPLE: u16 = 2;
+pub const CTA_EXPECT_MASK: u16 = 3;
+pub const CTA_EXPECT_TIMEOUT: u16 = 4;
+pub const CTA_EXPECT_ID: u16 = 5;
+
+impl Nla for ExpectAttribute {
+    fn value_len(&self) -> usize {
+        match self {
+            Self::Master(nlas) | Self::Tuple(nlas) | Self::Mask(nlas) => attributes_len(nlas),
+            Self::Timeout(v) | Self::Id(v) => size_of_val(v),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Self::Master(_) => CTA_EXPECT_MASTER,
+            Self::Tuple(_) => CTA_EXPECT_TUPLE,
+            Self::Mask(_) => CTA_EXPECT_MASK,
+            Self::Timeout(_) => CTA_EXPECT_TIMEOUT,
+            Self::Id(_) => CTA_EXPECT_ID,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Self::Master(nlas) | Self::Tuple(nlas) | Self::Mask(nlas) => {
+                nlas.as_slice().emit(buffer)
+            }
+            Self::Timeout(v) | Self::Id(v) => emit_u32_be(buffer, *v).unwrap(),
+        }
+    }
+
+    fn is_nested(&self) -> bool {
+        matches!(self, Self::Master(_) | Self::Tuple(_) | Self::Mask(_))
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for ExpectAttribute {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+        Ok(match buf.kind() {
+            CTA_EXPECT_MASTER => {
+                Self::Master(parse_nlas(payload, "failed to parse CTA_EXPECT_MASTER")?)
+            }
+            CTA_EXPECT_TUPLE => {
+                Self::Tuple(parse_nlas(payload, "failed to parse CTA_EXPECT_TUPLE")?)
+            }
+            CTA_EXPECT_MASK => Self::Mask(parse_nlas(payload, "failed to parse CTA_EXPECT_MASK")?),
+            CTA_EXPECT_TIMEOUT => Self::Timeout(
+                read_int!(be u32, payload).context("invalid CTA_EXPECT_TIMEOUT value")?,
+            ),
+            CTA_EXPECT_ID => {
+                Self::Id(read_int!(be u32, payload).context("invalid CTA_EXPECT_ID value")?)
+            }
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
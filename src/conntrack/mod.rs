@@ -0,0 +1,2645 @@
+use crate::{
+    KernelOrder, NetlinkProtocol, StringPolicy, attributes_len, emit_header_and_nlas,
+    for_each_message, parse_u32_flagged, sort_canonical,
+};
+use netlink_packet_core::{
+    ErrorContext, NLA_HEADER_SIZE, NLA_TYPE_MASK, NlaBuffer, NlasIterator, emit_u16_be, emit_u32,
+    emit_u32_be, emit_u64_be, parse_ip, parse_string,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::ops::ControlFlow;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use netlink_packet_core::{
+    DecodeError, DefaultNla, Emitable, NetlinkDeserializable, NetlinkHeader, NetlinkMessage,
+    NetlinkPayload, NetlinkSerializable, Nla, Parseable, buffer, fields, getter, setter,
+};
+
+const NFGENMSG_LEN: usize = 4;
+
+// We use the `buffer!` macro to create a safe, zero-copy wrapper around a byte slice.
+// It automatically generates getter and setter methods for the fields we define.
+buffer!(NfgenmsgBuffer(NFGENMSG_LEN) {
+    nfgen_family: (u8, 0),
+    version: (u8, 1),
+    resource_id: (u16, 2..4),
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nfgenmsg {
+    pub nfgen_family: u8,
+    pub version: u8,
+    // `res_id` is a `__be16`, it is swapped when emitted and parsed.
+    pub resource_id: u16,
+}
+
+// Implement `Emitable` to define how to write `Nfgenmsg` to a byte buffer.
+impl Emitable for Nfgenmsg {
+    fn buffer_len(&self) -> usize {
+        NFGENMSG_LEN
+    }
+
+    fn emit(&self, buffer: &mut [u8]) {
+        let mut buf = NfgenmsgBuffer::new(buffer);
+        buf.set_nfgen_family(self.nfgen_family);
+        buf.set_version(self.version);
+        buf.set_resource_id(self.resource_id.to_be());
+    }
+}
+
+// Implement `Parseable` to define how to read `BvgGenMsg` from a byte buffer.
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NfgenmsgBuffer<&'a T>> for Nfgenmsg {
+    fn parse(buf: &NfgenmsgBuffer<&'a T>) -> Result<Self, DecodeError> {
+        Ok(Nfgenmsg {
+            nfgen_family: buf.nfgen_family(),
+            version: buf.version(),
+            resource_id: u16::from_be(buf.resource_id()),
+        })
+    }
+}
+
+// Returned by `Nfgenmsg::set_resource_id()` for a value ctnetlink would not
+// expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnexpectedResourceId(pub u16);
+
+impl std::error::Error for UnexpectedResourceId {}
+
+impl fmt::Display for UnexpectedResourceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unexpected resource_id {}, conntrack requests use 0 outside of zones",
+            self.0
+        )
+    }
+}
+
+impl Nfgenmsg {
+    // The checked way of setting `resource_id`: conntrack requests carry 0
+    // unless `zones` are in use. The field itself stays public for anything
+    // else, e.g. the CPU index of the per-CPU stats.
+    pub fn set_resource_id(
+        &mut self,
+        resource_id: u16,
+        zones: bool,
+    ) -> Result<(), UnexpectedResourceId> {
+        if resource_id != 0 && !zones {
+            return Err(UnexpectedResourceId(resource_id));
+        }
+        self.resource_id = resource_id;
+        Ok(())
+    }
+}
+
+// Top level message
+#[derive(PartialEq, Debug)]
+pub enum NetfilterMessage {
+    ConntrackGet {
+        header: Nfgenmsg,
+        nlas: Vec<ConntrackAttribute>,
+    },
+    // The kernel answers a dump with one `IPCTNL_MSG_CT_NEW` message per entry.
+    ConntrackNew {
+        header: Nfgenmsg,
+        nlas: Vec<ConntrackAttribute>,
+    },
+    // Sent to delete an entry, and by the kernel on the
+    // NFNLGRP_CONNTRACK_DESTROY group once an entry is gone.
+    ConntrackDelete {
+        header: Nfgenmsg,
+        nlas: Vec<ConntrackAttribute>,
+    },
+    // A message of a type this crate doesn't model, kept undecoded by
+    // `deserialize_or_raw()`: `payload` holds its attributes as they came.
+    Raw {
+        message_type: u16,
+        header: Nfgenmsg,
+        payload: Vec<u8>,
+    },
+}
+
+pub const NFNL_SUBSYS_CTNETLINK: u16 = 1;
+// enum cntl_msg_types
+pub const IPCTNL_MSG_CT_NEW: u16 = 0;
+pub const IPCTNL_MSG_CT_GET: u16 = 1;
+pub const IPCTNL_MSG_CT_DELETE: u16 = 2;
+pub const IPCTNL_MSG_CT_GET_CTRZERO: u16 = 3;
+pub const IPCTNL_MSG_CT_GET_STATS_CPU: u16 = 4;
+pub const IPCTNL_MSG_CT_GET_STATS: u16 = 5;
+pub const IPCTNL_MSG_CT_GET_DYING: u16 = 6;
+pub const IPCTNL_MSG_CT_GET_UNCONFIRMED: u16 = 7;
+pub const NETFILTER_CONNTRACK_NEW_MESSAGE_TYPE: u16 =
+    NFNL_SUBSYS_CTNETLINK << 8 | IPCTNL_MSG_CT_NEW;
+pub const NETFILTER_CONNTRACK_GET_MESSAGE_TYPE: u16 =
+    NFNL_SUBSYS_CTNETLINK << 8 | IPCTNL_MSG_CT_GET;
+pub const NETFILTER_CONNTRACK_DELETE_MESSAGE_TYPE: u16 =
+    NFNL_SUBSYS_CTNETLINK << 8 | IPCTNL_MSG_CT_DELETE;
+
+// The ctnetlink commands, i.e. the lower byte of `nlmsghdr.message_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConntrackMessageType {
+    New,
+    Get,
+    Delete,
+    GetCtrZero,
+    GetStatsCpu,
+    GetStats,
+    GetDying,
+    GetUnconfirmed,
+}
+
+impl TryFrom<u8> for ConntrackMessageType {
+    type Error = DecodeError;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        Ok(match u16::from(v) {
+            IPCTNL_MSG_CT_NEW => Self::New,
+            IPCTNL_MSG_CT_GET => Self::Get,
+            IPCTNL_MSG_CT_DELETE => Self::Delete,
+            IPCTNL_MSG_CT_GET_CTRZERO => Self::GetCtrZero,
+            IPCTNL_MSG_CT_GET_STATS_CPU => Self::GetStatsCpu,
+            IPCTNL_MSG_CT_GET_STATS => Self::GetStats,
+            IPCTNL_MSG_CT_GET_DYING => Self::GetDying,
+            IPCTNL_MSG_CT_GET_UNCONFIRMED => Self::GetUnconfirmed,
+            other => {
+                return Err(DecodeError::from(format!(
+                    "unknown ctnetlink message type: {}",
+                    other
+                )));
+            }
+        })
+    }
+}
+
+impl ConntrackMessageType {
+    // Split a full `nlmsghdr.message_type`, refusing other subsystems.
+    pub fn from_message_type(message_type: u16) -> Result<Self, DecodeError> {
+        if message_type >> 8 != NFNL_SUBSYS_CTNETLINK {
+            return Err(DecodeError::from(format!(
+                "message type {:#06x} is not a ctnetlink message",
+                message_type
+            )));
+        }
+        Self::try_from(message_type as u8)
+    }
+
+    pub fn message_type(self) -> u16 {
+        let command = match self {
+            Self::New => IPCTNL_MSG_CT_NEW,
+            Self::Get => IPCTNL_MSG_CT_GET,
+            Self::Delete => IPCTNL_MSG_CT_DELETE,
+            Self::GetCtrZero => IPCTNL_MSG_CT_GET_CTRZERO,
+            Self::GetStatsCpu => IPCTNL_MSG_CT_GET_STATS_CPU,
+            Self::GetStats => IPCTNL_MSG_CT_GET_STATS,
+            Self::GetDying => IPCTNL_MSG_CT_GET_DYING,
+            Self::GetUnconfirmed => IPCTNL_MSG_CT_GET_UNCONFIRMED,
+        };
+        NFNL_SUBSYS_CTNETLINK << 8 | command
+    }
+}
+
+// for serializing
+impl NetlinkSerializable for NetfilterMessage {
+    fn message_type(&self) -> u16 {
+        match self {
+            Self::ConntrackGet { .. } => NETFILTER_CONNTRACK_GET_MESSAGE_TYPE,
+            Self::ConntrackNew { .. } => NETFILTER_CONNTRACK_NEW_MESSAGE_TYPE,
+            Self::ConntrackDelete { .. } => NETFILTER_CONNTRACK_DELETE_MESSAGE_TYPE,
+            Self::Raw { message_type, .. } => *message_type,
+        }
+    }
+
+    fn buffer_len(&self) -> usize {
+        match self {
+            Self::ConntrackGet { header, nlas }
+            | Self::ConntrackNew { header, nlas }
+            | Self::ConntrackDelete { header, nlas } => header.buffer_len() + attributes_len(nlas),
+            Self::Raw {
+                header, payload, ..
+            } => header.buffer_len() + payload.len(),
+        }
+    }
+
+    fn serialize(&self, buffer: &mut [u8]) {
+        match self {
+            Self::ConntrackGet { header, nlas }
+            | Self::ConntrackNew { header, nlas }
+            | Self::ConntrackDelete { header, nlas } => emit_header_and_nlas(header, nlas, buffer),
+            Self::Raw {
+                header, payload, ..
+            } => {
+                header.emit(buffer);
+                buffer[header.buffer_len()..].copy_from_slice(payload);
+            }
+        }
+    }
+}
+
+// The kernel sends exactly one CTA_TUPLE_ORIG per entry. This decides what
+// happens when a buffer carries more than one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateTuplePolicy {
+    // Refuse the message.
+    #[default]
+    Strict,
+    // Keep the first tuple and drop the others.
+    Lenient,
+}
+
+// for deserializing the message
+impl NetlinkDeserializable for NetfilterMessage {
+    type Error = DecodeError;
+
+    fn deserialize(header: &NetlinkHeader, payload: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize_with_policy(header, payload, DuplicateTuplePolicy::default())
+    }
+}
+
+impl NetfilterMessage {
+    pub fn deserialize_with_policy(
+        header: &NetlinkHeader,
+        payload: &[u8],
+        policy: DuplicateTuplePolicy,
+    ) -> Result<Self, DecodeError> {
+        Self::deserialize_inner(header, payload, policy, None)
+    }
+
+    // Decode a message even if it carries attributes this crate doesn't
+    // model, keeping them as `ConntrackAttribute::Other`. The kind and value
+    // length of each of them is appended to `unknown`, which helps finding
+    // out what a newer kernel sends.
+    pub fn deserialize_collecting_unknown(
+        header: &NetlinkHeader,
+        payload: &[u8],
+        unknown: &mut Vec<(u16, usize)>,
+    ) -> Result<Self, DecodeError> {
+        Self::deserialize_inner(
+            header,
+            payload,
+            DuplicateTuplePolicy::default(),
+            Some(unknown),
+        )
+    }
+
+    // Decode a message like `deserialize()`, except that a message type it
+    // doesn't know (another netfilter subsystem, a ctnetlink command
+    // without a variant...) is returned as `NetfilterMessage::Raw` instead
+    // of failing, for sniffers to show whatever goes by.
+    pub fn deserialize_or_raw(header: &NetlinkHeader, payload: &[u8]) -> Result<Self, DecodeError> {
+        let known = matches!(
+            ConntrackMessageType::from_message_type(header.message_type),
+            Ok(ConntrackMessageType::Get
+                | ConntrackMessageType::New
+                | ConntrackMessageType::Delete)
+        );
+        if known {
+            return <Self as NetlinkDeserializable>::deserialize(header, payload);
+        }
+        Ok(Self::Raw {
+            message_type: header.message_type,
+            header: parse_nfgenmsg(payload)?,
+            payload: payload[NFGENMSG_LEN..].to_vec(),
+        })
+    }
+
+    fn deserialize_inner(
+        header: &NetlinkHeader,
+        payload: &[u8],
+        policy: DuplicateTuplePolicy,
+        mut unknown: Option<&mut Vec<(u16, usize)>>,
+    ) -> Result<Self, DecodeError> {
+        let nfgen_header = parse_nfgenmsg(payload)?;
+
+        // Parse netlink attributes
+        let error_msg = "failed to parse Conntrack attributes";
+        let mut conntrack_attributes = Vec::new();
+        for (offset, nlas) in nlas_with_offset(&payload[NFGENMSG_LEN..]) {
+            // Report offsets from the start of the payload, nfgenmsg included.
+            let at_offset = |e: DecodeError| {
+                e.context(format!("{} at offset {}", error_msg, NFGENMSG_LEN + offset))
+            };
+            let attribute = nlas
+                .and_then(|nlas| ConntrackAttribute::parse(&nlas))
+                .map_err(at_offset)?;
+            if let ConntrackAttribute::Other(nla) = &attribute {
+                // `DefaultNla` keeps the NLA_F_* flags in its kind.
+                let kind = nla.kind() & NLA_TYPE_MASK;
+                let Some(unknown) = unknown.as_deref_mut() else {
+                    let e = format!("invalid NLA kind: {}", kind);
+                    return Err(at_offset(DecodeError::from(e)));
+                };
+                unknown.push((kind, nla.value_len()));
+            }
+
+            let is_orig =
+                |nla: &ConntrackAttribute| matches!(nla, ConntrackAttribute::CtaTupleOrig(_));
+            if is_orig(&attribute) && conntrack_attributes.iter().any(is_orig) {
+                match policy {
+                    DuplicateTuplePolicy::Strict => {
+                        return Err(DecodeError::from("duplicate CTA_TUPLE_ORIG attribute"));
+                    }
+                    DuplicateTuplePolicy::Lenient => continue,
+                }
+            }
+            conntrack_attributes.push(attribute);
+        }
+
+        // We use the main `nlmsghdr.message_type` to decide which enum variant
+        // to construct. This is the counterpart to `message_type()` in the
+        // `NetlinkSerializable` impl.
+        match ConntrackMessageType::from_message_type(header.message_type)? {
+            ConntrackMessageType::Get => Ok(Self::ConntrackGet {
+                header: nfgen_header,
+                nlas: conntrack_attributes,
+            }),
+            ConntrackMessageType::New => Ok(Self::ConntrackNew {
+                header: nfgen_header,
+                nlas: conntrack_attributes,
+            }),
+            ConntrackMessageType::Delete => Ok(Self::ConntrackDelete {
+                header: nfgen_header,
+                nlas: conntrack_attributes,
+            }),
+            other => Err(DecodeError::from(format!(
+                "Unsupported message type for conntrack: {:?}",
+                other
+            ))),
+        }
+    }
+
+    pub fn conntrack_get(header: Nfgenmsg, nlas: Vec<ConntrackAttribute>) -> Self {
+        Self::ConntrackGet { header, nlas }
+    }
+
+    pub fn conntrack_new(header: Nfgenmsg, nlas: Vec<ConntrackAttribute>) -> Self {
+        Self::ConntrackNew { header, nlas }
+    }
+
+    pub fn conntrack_delete(header: Nfgenmsg, nlas: Vec<ConntrackAttribute>) -> Self {
+        Self::ConntrackDelete { header, nlas }
+    }
+
+    pub fn header(&self) -> &Nfgenmsg {
+        match self {
+            Self::ConntrackGet { header, .. }
+            | Self::ConntrackNew { header, .. }
+            | Self::ConntrackDelete { header, .. }
+            | Self::Raw { header, .. } => header,
+        }
+    }
+
+    // Empty for a `Raw` message, whose attributes were never decoded.
+    pub fn nlas(&self) -> &[ConntrackAttribute] {
+        match self {
+            Self::ConntrackGet { nlas, .. }
+            | Self::ConntrackNew { nlas, .. }
+            | Self::ConntrackDelete { nlas, .. } => nlas,
+            Self::Raw { .. } => &[],
+        }
+    }
+
+    // `None` for a `Raw` message, which has no decoded attributes to change.
+    pub fn nlas_mut(&mut self) -> Option<&mut Vec<ConntrackAttribute>> {
+        match self {
+            Self::ConntrackGet { nlas, .. }
+            | Self::ConntrackNew { nlas, .. }
+            | Self::ConntrackDelete { nlas, .. } => Some(nlas),
+            Self::Raw { .. } => None,
+        }
+    }
+
+    // Reorder the attributes the way the kernel dumps them, e.g. before
+    // comparing an entry built by hand with a dumped one byte for byte.
+    pub fn canonicalize(&mut self) {
+        if let Some(nlas) = self.nlas_mut() {
+            sort_canonical(nlas);
+        }
+    }
+
+    // Rewrite IPv4-mapped IPv6 addresses (`::ffff:10.0.0.1`) of the tuples
+    // as the IPv4 addresses they stand for.
+    pub fn normalize_mapped_addresses(&mut self) {
+        if let Some(nlas) = self.nlas_mut() {
+            unmap_addresses(nlas);
+        }
+    }
+
+    // Whether both messages describe the same thing, whatever the order of
+    // their attributes and whether their IPv4 addresses are mapped into
+    // IPv6, e.g. when comparing entries of a dual-stack host taken from
+    // different sources. The family of the header is ignored for that
+    // reason, the addresses carry it.
+    pub fn semantic_eq(&self, other: &Self) -> bool {
+        if let Self::Raw { .. } = self {
+            return self == other;
+        }
+        let normalized = |message: &Self| {
+            let mut nlas = message.nlas().to_vec();
+            unmap_addresses(&mut nlas);
+            sort_canonical(&mut nlas);
+            nlas
+        };
+        let (header, other_header) = (self.header(), other.header());
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+            && header.version == other_header.version
+            && header.resource_id == other_header.resource_id
+            && normalized(self) == normalized(other)
+    }
+
+    // Drop every attribute of the given kind, e.g. CTA_ID before using a
+    // dumped entry as the template of a new one. Lengths are only computed
+    // again by `finalize()`.
+    pub fn without_attribute(mut self, kind: u16) -> Self {
+        if let Some(nlas) = self.nlas_mut() {
+            nlas.retain(|nla| nla.kind() != kind);
+        }
+        self
+    }
+
+    // The tuple of the entry for `direction`, if it carries a complete one.
+    pub fn tuple(&self, direction: Direction) -> Option<FiveTuple> {
+        self.nlas().iter().find_map(|nla| match (direction, nla) {
+            (Direction::Original, ConntrackAttribute::CtaTupleOrig(tuples))
+            | (Direction::Reply, ConntrackAttribute::CtaTupleReply(tuples)) => {
+                FiveTuple::from_tuples(tuples)
+            }
+            _ => None,
+        })
+    }
+
+    pub fn orig_tuple(&self) -> Option<FiveTuple> {
+        self.tuple(Direction::Original)
+    }
+
+    // The ports of the original tuple, `None` for a protocol without any
+    // (ICMP, GRE...) even if the tuple carries port attributes anyway, so
+    // that an ICMP id or GRE key is never taken for a port.
+    pub fn src_port(&self) -> Option<u16> {
+        let tuple = self.orig_tuple()?;
+        Protocol::from(tuple.protocol)
+            .has_ports()
+            .then_some(tuple.src_port?)
+    }
+
+    pub fn dst_port(&self) -> Option<u16> {
+        let tuple = self.orig_tuple()?;
+        Protocol::from(tuple.protocol)
+            .has_ports()
+            .then_some(tuple.dst_port?)
+    }
+
+    // The conntrack zone of the entry, if it isn't in the default one.
+    pub fn zone(&self) -> Option<Zone> {
+        let tuple_zone = |tuples: &[Tuple]| {
+            tuples.iter().find_map(|tuple| match tuple {
+                Tuple::Zone(id) => Some(*id),
+                _ => None,
+            })
+        };
+        self.nlas().iter().find_map(|nla| {
+            let (id, dir) = match nla {
+                ConntrackAttribute::CtaZone(id) => (*id, ZoneDir::Default),
+                ConntrackAttribute::CtaTupleOrig(tuples) => (tuple_zone(tuples)?, ZoneDir::Orig),
+                ConntrackAttribute::CtaTupleReply(tuples) => (tuple_zone(tuples)?, ZoneDir::Reply),
+                _ => return None,
+            };
+            Some(Zone { id, dir })
+        })
+    }
+
+    // The CTA_COUNTERS_ORIG/REPLY of the entry for `direction`, if any.
+    pub fn counters(&self, direction: Direction) -> Option<ConntrackCounters> {
+        self.nlas().iter().find_map(|nla| match (direction, nla) {
+            (Direction::Original, ConntrackAttribute::CtaCountersOrig(counters))
+            | (Direction::Reply, ConntrackAttribute::CtaCountersReply(counters)) => {
+                Some(ConntrackCounters::from_counters(counters))
+            }
+            _ => None,
+        })
+    }
+
+    // Indices of the connlabels set on the entry. CTA_LABELS is the kernel's
+    // label bitmap, bit `n` being bit `n % 8` of byte `n / 8`.
+    pub fn labels(&self) -> Vec<usize> {
+        self.nlas()
+            .iter()
+            .filter_map(|nla| match nla {
+                ConntrackAttribute::CtaLabels(bits) => Some(bits),
+                _ => None,
+            })
+            .flat_map(|bits| {
+                bits.iter().enumerate().flat_map(|(i, byte)| {
+                    (0..8)
+                        .filter(move |bit| byte & (1 << bit) != 0)
+                        .map(move |bit| i * 8 + bit)
+                })
+            })
+            .collect()
+    }
+
+    // The CTA_ID of the entry. The kernel only deletes the entry matching
+    // both the tuple and this id when a DELETE carries one.
+    pub fn id(&self) -> Option<u32> {
+        self.nlas().iter().find_map(|nla| match nla {
+            ConntrackAttribute::CtaId(id) => Some(*id),
+            _ => None,
+        })
+    }
+
+    // The CTA_STATUS of the entry, if it carries one.
+    pub fn status(&self) -> Option<ConnStatus> {
+        self.nlas().iter().find_map(|nla| match nla {
+            ConntrackAttribute::CtaStatus(status) => Some(ConnStatus(*status)),
+            _ => None,
+        })
+    }
+
+    // The CTA_TIMEOUT of the entry, in seconds, if it carries one.
+    pub fn timeout(&self) -> Option<u32> {
+        self.nlas().iter().find_map(|nla| match nla {
+            ConntrackAttribute::CtaTimeout(timeout) => Some(*timeout),
+            _ => None,
+        })
+    }
+
+    // The CTA_MARK of the entry, if it carries one.
+    pub fn mark(&self) -> Option<u32> {
+        self.nlas().iter().find_map(|nla| match nla {
+            ConntrackAttribute::CtaMark(mark) => Some(*mark),
+            _ => None,
+        })
+    }
+
+    // The CTA_PROTOINFO_TCP_STATE of the entry, if it carries one.
+    pub fn tcp_state(&self) -> Option<u8> {
+        self.nlas()
+            .iter()
+            .filter_map(|nla| match nla {
+                ConntrackAttribute::CtaProtoInfo(infos) => Some(infos),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|info| match info {
+                ProtoInfo::TCP(tcp) => Some(tcp),
+                _ => None,
+            })
+            .flatten()
+            .find_map(|tcp| match tcp {
+                ProtoInfoTCP::State(state) => Some(*state),
+                _ => None,
+            })
+    }
+
+    // Check that the addresses of the tuples belong to the family of the
+    // nfgenmsg, which the kernel uses to decode them: an IPv6 address in an
+    // AF_INET request gets it rejected. AF_UNSPEC matches anything. The same
+    // goes for NAT ranges, whose addresses must also match their kind.
+    pub fn validate(&self) -> Result<(), DecodeError> {
+        let family = self.header().nfgen_family;
+        let family_of = |addr: &IpAddr| match addr {
+            IpAddr::V4(_) => AF_INET,
+            IpAddr::V6(_) => AF_INET6,
+        };
+        let check_family = |addr: &IpAddr| {
+            if family != AF_UNSPEC && family != family_of(addr) {
+                return Err(DecodeError::from(format!(
+                    "address {} does not belong to family {}",
+                    addr, family
+                )));
+            }
+            Ok(())
+        };
+
+        let addresses = self
+            .nlas()
+            .iter()
+            .filter_map(|nla| match nla {
+                ConntrackAttribute::CtaTupleOrig(tuples)
+                | ConntrackAttribute::CtaTupleReply(tuples) => Some(tuples),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|tuple| match tuple {
+                Tuple::Ip(addresses) => Some(addresses),
+                _ => None,
+            })
+            .flatten();
+        for address in addresses {
+            let (IPTuple::SourceAddress(addr) | IPTuple::DestinationAddress(addr)) = address;
+            check_family(addr)?;
+        }
+
+        // A CTA_NAT_V4_* holding an IPv6 address would go out with 16 bytes
+        // under an IPv4 kind.
+        let nats = self.nlas().iter().filter_map(|nla| match nla {
+            ConntrackAttribute::CtaNatSrc(nats) | ConntrackAttribute::CtaNatDst(nats) => Some(nats),
+            _ => None,
+        });
+        for nat in nats.flatten() {
+            let (addr, expected) = match nat {
+                Nat::V4MinIp(addr) | Nat::V4MaxIp(addr) => (addr, AF_INET),
+                Nat::V6MinIp(addr) | Nat::V6MaxIp(addr) => (addr, AF_INET6),
+                Nat::Proto(_) => continue,
+            };
+            if family_of(addr) != expected {
+                return Err(DecodeError::from(format!(
+                    "address {} does not fit NAT attribute kind {}",
+                    addr,
+                    nat.kind()
+                )));
+            }
+            check_family(addr)?;
+        }
+        Ok(())
+    }
+
+    // Check the entry against what the kernel guarantees for the entries it
+    // dumps, e.g. before sending one built by hand. Returns every violation
+    // found.
+    pub fn check_invariants(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        let has_reply = self
+            .nlas()
+            .iter()
+            .any(|nla| matches!(nla, ConntrackAttribute::CtaTupleReply(_)));
+        if self.status().is_some_and(ConnStatus::is_seen_reply) && !has_reply {
+            errors.push("IPS_SEEN_REPLY is set but there is no CTA_TUPLE_REPLY".to_string());
+        }
+
+        let mut protocol = None;
+        for direction in [Direction::Original, Direction::Reply] {
+            let Some(tuple) = self.tuple(direction) else {
+                continue;
+            };
+            protocol.get_or_insert(Protocol::from(tuple.protocol));
+            let needs_ports = matches!(
+                Protocol::from(tuple.protocol),
+                Protocol::Tcp | Protocol::Udp
+            );
+            if needs_ports && (tuple.src_port.is_none() || tuple.dst_port.is_none()) {
+                errors.push(format!("{:?} tuple is missing a port", direction));
+            }
+        }
+
+        let infos = self
+            .nlas()
+            .iter()
+            .filter_map(|nla| match nla {
+                ConntrackAttribute::CtaProtoInfo(infos) => Some(infos),
+                _ => None,
+            })
+            .flatten();
+        for info in infos {
+            let expected = match info {
+                ProtoInfo::TCP(_) => Protocol::Tcp,
+                ProtoInfo::DCCP(_) => Protocol::Dccp,
+                ProtoInfo::SCTP(_) => Protocol::Sctp,
+            };
+            if let Some(protocol) = protocol.filter(|protocol| *protocol != expected) {
+                errors.push(format!(
+                    "CTA_PROTOINFO is for {:?} but the tuple is {:?}",
+                    expected, protocol
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+pub const AF_UNSPEC: u8 = 0;
+pub const AF_INET: u8 = 2;
+pub const AF_INET6: u8 = 10;
+
+// Build the finalized request listing the conntrack table (`conntrack -L`).
+// `family` is `AF_INET` or `AF_INET6` to restrict the dump to one family, or
+// `AF_UNSPEC` to dump all of them.
+pub fn conntrack_dump_request(family: u8, seq: u32) -> NetlinkMessage<NetfilterMessage> {
+    let conntrack_get_message = NetfilterMessage::ConntrackGet {
+        header: Nfgenmsg {
+            nfgen_family: family,
+            version: 0,
+            resource_id: 0,
+        },
+        nlas: vec![],
+    };
+    let mut packet = NetlinkMessage::from(conntrack_get_message);
+    packet.header.flags = netlink_packet_core::NLM_F_REQUEST | netlink_packet_core::NLM_F_DUMP;
+    packet.header.sequence_number = seq;
+    packet.finalize();
+    packet
+}
+
+// `conntrack -L` without `-f`: the kernel walks the whole table and returns
+// IPv4 and IPv6 entries alike. With a family set it skips every entry of the
+// other family, and filters like CTA_MARK only apply on top of that.
+pub fn conntrack_dump_all_request(seq: u32) -> NetlinkMessage<NetfilterMessage> {
+    conntrack_dump_request(AF_UNSPEC, seq)
+}
+
+// Split the answer to a dump request into its entries. The kernel packs
+// several messages in a datagram, each one starting at the aligned end of
+// the previous one, and ends the dump with NLMSG_DONE. Whatever follows it
+// is ignored. Fails if `buf` ends before the NLMSG_DONE, or on an
+// NLMSG_ERROR.
+pub fn parse_dump(buf: &[u8]) -> Result<Vec<NetlinkMessage<NetfilterMessage>>, DecodeError> {
+    let mut entries = Vec::new();
+    let end = for_each_message(buf, |message: NetlinkMessage<NetfilterMessage>| {
+        match message.payload {
+            NetlinkPayload::InnerMessage(_) => entries.push(message),
+            NetlinkPayload::Done(_) => return ControlFlow::Break(Ok(())),
+            NetlinkPayload::Error(e) => {
+                return ControlFlow::Break(Err(DecodeError::from(format!("dump failed: {}", e))));
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    })?;
+    match end {
+        Some(result) => result.map(|()| entries),
+        None => Err(DecodeError::from(format!(
+            "dump ends without NLMSG_DONE after {} entries",
+            entries.len()
+        ))),
+    }
+}
+
+// The conntrack messages, requests and answers, of an nlmon capture.
+#[cfg(feature = "pcap")]
+pub fn read_pcap<R: std::io::Read>(
+    input: R,
+) -> Result<Vec<NetfilterMessage>, crate::pcap::PcapError> {
+    let messages =
+        crate::pcap::read_messages::<NetfilterMessage, R>(input, crate::pcap::NETLINK_NETFILTER)?;
+    Ok(messages
+        .into_iter()
+        .filter_map(|message| match message.payload {
+            NetlinkPayload::InnerMessage(message) => Some(message),
+            _ => None,
+        })
+        .collect())
+}
+
+// to do stuff like `NetlinkMessage::from(my_beverage_message)`.
+impl From<NetfilterMessage> for NetlinkPayload<NetfilterMessage> {
+    fn from(message: NetfilterMessage) -> Self {
+        NetlinkPayload::InnerMessage(message)
+    }
+}
+
+// Every ctnetlink message carries the subsystem id in the upper byte of its
+// message type.
+impl NetlinkProtocol for NetfilterMessage {
+    fn owns_message_type(message_type: u16) -> bool {
+        message_type >> 8 == NFNL_SUBSYS_CTNETLINK
+    }
+
+    fn deserialize(buf: &[u8]) -> Result<NetlinkMessage<Self>, DecodeError> {
+        NetlinkMessage::deserialize(buf)
+    }
+}
+
+// The nfgenmsg every nfnetlink message starts with.
+fn parse_nfgenmsg(payload: &[u8]) -> Result<Nfgenmsg, DecodeError> {
+    if payload.len() < NFGENMSG_LEN {
+        return Err(DecodeError::from(
+            "Payload is too short for NFGENMSG header",
+        ));
+    }
+    Nfgenmsg::parse(&NfgenmsgBuffer::new(&payload[..NFGENMSG_LEN]))
+}
+
+// Iterate over the attributes of `payload` together with the offset at which
+// each of them starts, so that errors can point at the faulty attribute.
+fn nlas_with_offset(
+    payload: &[u8],
+) -> impl Iterator<Item = (usize, Result<NlaBuffer<&[u8]>, DecodeError>)> {
+    let mut offset = 0;
+    NlasIterator::new(payload).map(move |nla| {
+        let start = offset;
+        // A trailing fragment too short to even hold the length and kind
+        // fields is a truncated message, say so rather than reporting a
+        // generic buffer size error.
+        let left = payload.len() - start;
+        if left < NLA_HEADER_SIZE {
+            let e = format!("truncated attribute header: only {} bytes left", left);
+            return (start, Err(DecodeError::from(e)));
+        }
+        if let Ok(nla) = &nla {
+            offset += (nla.length() as usize + 3) & !3;
+        }
+        (start, nla)
+    })
+}
+
+// Parse every attribute of a nested block, prefixing errors with `error_msg`
+// and the offset of the failing attribute within the block.
+fn parse_nlas<T>(payload: &[u8], error_msg: &str) -> Result<Vec<T>, DecodeError>
+where
+    T: for<'a> Parseable<NlaBuffer<&'a [u8]>>,
+{
+    let mut nlas = Vec::new();
+    for (offset, nla) in nlas_with_offset(payload) {
+        let nla = nla
+            .and_then(|nla| T::parse(&nla))
+            .map_err(|e| e.context(format!("{} at offset {}", error_msg, offset)))?;
+        nlas.push(nla);
+    }
+    Ok(nlas)
+}
+
+// Like `parse_nlas()`, for the blocks of 64-bit values (counters,
+// timestamps). The kernel may put an empty `pad` attribute in front of a
+// value to align it, those are dropped.
+fn parse_nlas_skipping_pad<T>(
+    payload: &[u8],
+    pad: u16,
+    error_msg: &str,
+) -> Result<Vec<T>, DecodeError>
+where
+    T: for<'a> Parseable<NlaBuffer<&'a [u8]>>,
+{
+    let mut nlas = Vec::new();
+    for (offset, nla) in nlas_with_offset(payload) {
+        let nla = nla
+            .and_then(|nla| match nla.kind() {
+                kind if kind == pad => Ok(None),
+                _ => T::parse(&nla).map(Some),
+            })
+            .map_err(|e| e.context(format!("{} at offset {}", error_msg, offset)))?;
+        nlas.extend(nla);
+    }
+    Ok(nlas)
+}
+
+// -----------ConntrackAttribute stuff starts-----------------------
+#[derive(PartialEq, Debug, Clone)]
+pub enum ConntrackAttribute {
+    CtaTupleOrig(Vec<Tuple>),
+    CtaTupleReply(Vec<Tuple>),
+    CtaProtoInfo(Vec<ProtoInfo>),
+    CtaMark(u32),
+    CtaMarkMask(u32),
+    CtaStatus(u32),
+    CtaTimeout(u32),
+    CtaId(u32),
+    CtaZone(u16),
+    CtaLabels(Vec<u8>),
+    CtaFilter(Vec<Filter>),
+    CtaCountersOrig(Vec<Counter>),
+    CtaCountersReply(Vec<Counter>),
+    CtaSecCtx(Vec<SecCtx>),
+    CtaTimestamp(Vec<Timestamp>),
+    CtaHelp(Vec<Help>),
+    CtaNatSrc(Vec<Nat>),
+    CtaNatDst(Vec<Nat>),
+    // Any attribute this crate doesn't model. Only kept when decoding with
+    // `NetfilterMessage::deserialize_collecting_unknown()`, plain decoding
+    // rejects it.
+    Other(DefaultNla),
+}
+pub const CTA_TUPLE_ORIG: u16 = 1;
+pub const CTA_TUPLE_REPLY: u16 = 2;
+pub const CTA_STATUS: u16 = 3;
+pub const CTA_PROTOINFO: u16 = 4;
+pub const CTA_HELP: u16 = 5;
+pub const CTA_NAT_SRC: u16 = 6;
+pub const CTA_TIMEOUT: u16 = 7;
+pub const CTA_MARK: u16 = 8;
+pub const CTA_COUNTERS_ORIG: u16 = 9;
+pub const CTA_COUNTERS_REPLY: u16 = 10;
+pub const CTA_ID: u16 = 12;
+pub const CTA_NAT_DST: u16 = 13;
+pub const CTA_ZONE: u16 = 18;
+pub const CTA_SECCTX: u16 = 19;
+pub const CTA_TIMESTAMP: u16 = 20;
+pub const CTA_MARK_MASK: u16 = 21;
+pub const CTA_LABELS: u16 = 22;
+pub const CTA_FILTER: u16 = 25;
+
+// Bits of CTA_STATUS
+pub const IPS_EXPECTED: u32 = 1;
+pub const IPS_SEEN_REPLY: u32 = 1 << 1;
+pub const IPS_ASSURED: u32 = 1 << 2;
+pub const IPS_CONFIRMED: u32 = 1 << 3;
+pub const IPS_SRC_NAT: u32 = 1 << 4;
+pub const IPS_DST_NAT: u32 = 1 << 5;
+pub const IPS_DYING: u32 = 1 << 9;
+
+// The value of CTA_STATUS with named accessors for the common bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConnStatus(pub u32);
+
+impl ConnStatus {
+    pub fn contains(self, bits: u32) -> bool {
+        self.0 & bits == bits
+    }
+
+    pub fn is_expected(self) -> bool {
+        self.contains(IPS_EXPECTED)
+    }
+
+    pub fn is_seen_reply(self) -> bool {
+        self.contains(IPS_SEEN_REPLY)
+    }
+
+    pub fn is_assured(self) -> bool {
+        self.contains(IPS_ASSURED)
+    }
+
+    pub fn is_confirmed(self) -> bool {
+        self.contains(IPS_CONFIRMED)
+    }
+
+    pub fn is_src_nat(self) -> bool {
+        self.contains(IPS_SRC_NAT)
+    }
+
+    pub fn is_dst_nat(self) -> bool {
+        self.contains(IPS_DST_NAT)
+    }
+
+    pub fn is_dying(self) -> bool {
+        self.contains(IPS_DYING)
+    }
+}
+
+fn unmap_addresses(nlas: &mut [ConntrackAttribute]) {
+    let tuples = nlas.iter_mut().filter_map(|nla| match nla {
+        ConntrackAttribute::CtaTupleOrig(tuples) | ConntrackAttribute::CtaTupleReply(tuples) => {
+            Some(tuples)
+        }
+        _ => None,
+    });
+    for tuple in tuples.flatten() {
+        if let Tuple::Ip(addresses) = tuple {
+            for address in addresses {
+                let (IPTuple::SourceAddress(addr) | IPTuple::DestinationAddress(addr)) = address;
+                *addr = addr.to_canonical();
+            }
+        }
+    }
+}
+
+impl Nla for ConntrackAttribute {
+    fn value_len(&self) -> usize {
+        match self {
+            Self::CtaTupleOrig(nlas) | Self::CtaTupleReply(nlas) => attributes_len(nlas),
+            Self::CtaProtoInfo(nlas) => attributes_len(nlas),
+            Self::CtaMark(v) => size_of_val(v),
+            Self::CtaMarkMask(v) => size_of_val(v),
+            Self::CtaStatus(v) => size_of_val(v),
+            Self::CtaTimeout(v) => size_of_val(v),
+            Self::CtaId(v) => size_of_val(v),
+            Self::CtaZone(v) => size_of_val(v),
+            Self::CtaLabels(v) => v.len(),
+            Self::CtaFilter(nlas) => attributes_len(nlas),
+            Self::CtaCountersOrig(nlas) => attributes_len(nlas),
+            Self::CtaCountersReply(nlas) => attributes_len(nlas),
+            Self::CtaSecCtx(nlas) => attributes_len(nlas),
+            Self::CtaTimestamp(nlas) => attributes_len(nlas),
+            Self::CtaHelp(nlas) => attributes_len(nlas),
+            Self::CtaNatSrc(nlas) | Self::CtaNatDst(nlas) => attributes_len(nlas),
+            Self::Other(nla) => nla.value_len(),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Self::CtaTupleOrig(_) => CTA_TUPLE_ORIG,
+            Self::CtaTupleReply(_) => CTA_TUPLE_REPLY,
+            Self::CtaProtoInfo(_) => CTA_PROTOINFO,
+            Self::CtaMark(_) => CTA_MARK,
+            Self::CtaMarkMask(_) => CTA_MARK_MASK,
+            Self::CtaStatus(_) => CTA_STATUS,
+            Self::CtaTimeout(_) => CTA_TIMEOUT,
+            Self::CtaId(_) => CTA_ID,
+            Self::CtaZone(_) => CTA_ZONE,
+            Self::CtaLabels(_) => CTA_LABELS,
+            Self::CtaFilter(_) => CTA_FILTER,
+            Self::CtaCountersOrig(_) => CTA_COUNTERS_ORIG,
+            Self::CtaCountersReply(_) => CTA_COUNTERS_REPLY,
+            Self::CtaSecCtx(_) => CTA_SECCTX,
+            Self::CtaTimestamp(_) => CTA_TIMESTAMP,
+            Self::CtaHelp(_) => CTA_HELP,
+            Self::CtaNatSrc(_) => CTA_NAT_SRC,
+            Self::CtaNatDst(_) => CTA_NAT_DST,
+            Self::Other(nla) => nla.kind(),
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Self::CtaTupleOrig(nlas) | Self::CtaTupleReply(nlas) => {
+                let mut len = 0;
+                for op in nlas {
+                    op.emit(&mut buffer[len..]);
+                    len += op.buffer_len();
+                }
+            }
+            Self::CtaProtoInfo(nlas) => {
+                let mut len = 0;
+                for op in nlas {
+                    op.emit(&mut buffer[len..]);
+                    len += op.buffer_len();
+                }
+            }
+            Self::CtaMark(v) => emit_u32_be(buffer, *v).unwrap(),
+            Self::CtaMarkMask(v) => emit_u32_be(buffer, *v).unwrap(),
+            Self::CtaStatus(v) => emit_u32_be(buffer, *v).unwrap(),
+            Self::CtaTimeout(v) => emit_u32_be(buffer, *v).unwrap(),
+            Self::CtaId(v) => emit_u32_be(buffer, *v).unwrap(),
+            Self::CtaZone(v) => emit_u16_be(buffer, *v).unwrap(),
+            Self::CtaLabels(v) => buffer[..v.len()].copy_from_slice(v),
+            Self::CtaFilter(nlas) => nlas.as_slice().emit(buffer),
+            Self::CtaCountersOrig(nlas) => nlas.as_slice().emit(buffer),
+            Self::CtaCountersReply(nlas) => nlas.as_slice().emit(buffer),
+            Self::CtaSecCtx(nlas) => nlas.as_slice().emit(buffer),
+            Self::CtaTimestamp(nlas) => nlas.as_slice().emit(buffer),
+            Self::CtaHelp(nlas) => nlas.as_slice().emit(buffer),
+            Self::CtaNatSrc(nlas) | Self::CtaNatDst(nlas) => nlas.as_slice().emit(buffer),
+            Self::Other(nla) => nla.emit_value(buffer),
+        }
+    }
+    fn is_nested(&self) -> bool {
+        // `DefaultNla` keeps NLA_F_NESTED in its kind, keep it on the wire.
+        if let ConntrackAttribute::Other(nla) = self {
+            return nla.is_nested();
+        }
+        matches!(
+            self,
+            ConntrackAttribute::CtaTupleOrig(_)
+                | ConntrackAttribute::CtaTupleReply(_)
+                | ConntrackAttribute::CtaProtoInfo(_)
+                | ConntrackAttribute::CtaFilter(_)
+                | ConntrackAttribute::CtaCountersOrig(_)
+                | ConntrackAttribute::CtaCountersReply(_)
+                | ConntrackAttribute::CtaSecCtx(_)
+                | ConntrackAttribute::CtaTimestamp(_)
+                | ConntrackAttribute::CtaHelp(_)
+                | ConntrackAttribute::CtaNatSrc(_)
+                | ConntrackAttribute::CtaNatDst(_)
+        )
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for ConntrackAttribute {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+        Ok(match buf.kind() {
+            CTA_TUPLE_ORIG => ConntrackAttribute::CtaTupleOrig(parse_nlas(
+                payload,
+                "failed to parse CTA_TUPLE_ORIG",
+            )?),
+            CTA_TUPLE_REPLY => ConntrackAttribute::CtaTupleReply(parse_nlas(
+                payload,
+                "failed to parse CTA_TUPLE_REPLY",
+            )?),
+            CTA_PROTOINFO => ConntrackAttribute::CtaProtoInfo(parse_nlas(
+                payload,
+                "failed to parse CTA_PROTOINFO",
+            )?),
+            CTA_MARK => ConntrackAttribute::CtaMark(
+                read_int!(be u32, payload).context("invalid CTA_MARK value")?,
+            ),
+            CTA_MARK_MASK => ConntrackAttribute::CtaMarkMask(
+                read_int!(be u32, payload).context("invalid CTA_MARK_MASK value")?,
+            ),
+            CTA_STATUS => ConntrackAttribute::CtaStatus(
+                read_int!(be u32, payload).context("invalid CTA_STATUS value")?,
+            ),
+            CTA_TIMEOUT => ConntrackAttribute::CtaTimeout(
+                read_int!(be u32, payload).context("invalid CTA_TIMEOUT value")?,
+            ),
+            CTA_ID => ConntrackAttribute::CtaId(
+                read_int!(be u32, payload).context("invalid CTA_ID value")?,
+            ),
+            CTA_ZONE => ConntrackAttribute::CtaZone(
+                read_int!(be u16, payload).context("invalid CTA_ZONE value")?,
+            ),
+            CTA_LABELS => ConntrackAttribute::CtaLabels(payload.to_vec()),
+            CTA_FILTER => {
+                ConntrackAttribute::CtaFilter(parse_nlas(payload, "failed to parse CTA_FILTER")?)
+            }
+            CTA_COUNTERS_ORIG => ConntrackAttribute::CtaCountersOrig(parse_nlas_skipping_pad(
+                payload,
+                CTA_COUNTERS_PAD,
+                "failed to parse CTA_COUNTERS_ORIG",
+            )?),
+            CTA_COUNTERS_REPLY => ConntrackAttribute::CtaCountersReply(parse_nlas_skipping_pad(
+                payload,
+                CTA_COUNTERS_PAD,
+                "failed to parse CTA_COUNTERS_REPLY",
+            )?),
+            CTA_TIMESTAMP => ConntrackAttribute::CtaTimestamp(parse_nlas_skipping_pad(
+                payload,
+                CTA_TIMESTAMP_PAD,
+                "failed to parse CTA_TIMESTAMP",
+            )?),
+            CTA_HELP => {
+                ConntrackAttribute::CtaHelp(parse_nlas(payload, "failed to parse CTA_HELP")?)
+            }
+            CTA_SECCTX => {
+                ConntrackAttribute::CtaSecCtx(parse_nlas(payload, "failed to parse CTA_SECCTX")?)
+            }
+            CTA_NAT_SRC => {
+                ConntrackAttribute::CtaNatSrc(parse_nlas(payload, "failed to parse CTA_NAT_SRC")?)
+            }
+            CTA_NAT_DST => {
+                ConntrackAttribute::CtaNatDst(parse_nlas(payload, "failed to parse CTA_NAT_DST")?)
+            }
+            _ => ConntrackAttribute::Other(DefaultNla::parse(buf)?),
+        })
+    }
+}
+// The order of ctnetlink_fill_info(): the tuples and zone, then what
+// ctnetlink_dump_info() adds (status, mark, secctx and id, then the timeout
+// and protocol info unless the entry is offloaded), and only then what
+// ctnetlink_dump_extinfo() adds: accounting, timestamp, helper and labels.
+// Attributes only found in requests come last.
+impl KernelOrder for ConntrackAttribute {
+    fn sort_key(&self) -> u16 {
+        match self {
+            Self::CtaTupleOrig(_) => 0,
+            Self::CtaTupleReply(_) => 1,
+            Self::CtaZone(_) => 2,
+            Self::CtaStatus(_) => 3,
+            Self::CtaMark(_) => 4,
+            Self::CtaSecCtx(_) => 5,
+            Self::CtaId(_) => 6,
+            Self::CtaTimeout(_) => 7,
+            Self::CtaProtoInfo(_) => 8,
+            Self::CtaCountersOrig(_) => 9,
+            Self::CtaCountersReply(_) => 10,
+            Self::CtaTimestamp(_) => 11,
+            Self::CtaHelp(_) => 12,
+            Self::CtaLabels(_) => 13,
+            Self::CtaMarkMask(_) => 14,
+            Self::CtaFilter(_) => 15,
+            Self::CtaNatSrc(_) => 16,
+            Self::CtaNatDst(_) => 17,
+            Self::Other(_) => 18,
+        }
+    }
+}
+// -----------ConntrackAttribute stuff ends-----------------------
+
+// -----------Tuple stuff starts-----------------------
+#[derive(PartialEq, Debug, Clone)]
+pub enum Tuple {
+    Ip(Vec<IPTuple>),
+    Proto(Vec<ProtoTuple>),
+    // The zone of a tuple, when the entry's zone only applies to this
+    // direction. See `NetfilterMessage::zone()`.
+    Zone(u16),
+}
+
+pub const CTA_TUPLE_IP: u16 = 1;
+pub const CTA_TUPLE_PROTO: u16 = 2;
+pub const CTA_TUPLE_ZONE: u16 = 3;
+impl Nla for Tuple {
+    fn value_len(&self) -> usize {
+        match self {
+            Tuple::Ip(nlas) => attributes_len(nlas),
+            Tuple::Proto(nlas) => attributes_len(nlas),
+            Tuple::Zone(v) => size_of_val(v),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Tuple::Ip(_) => CTA_TUPLE_IP,
+            Tuple::Proto(_) => CTA_TUPLE_PROTO,
+            Tuple::Zone(_) => CTA_TUPLE_ZONE,
+        }
+    }
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Tuple::Ip(nlas) => nlas.as_slice().emit(buffer),
+            Tuple::Proto(nlas) => nlas.as_slice().emit(buffer),
+            Tuple::Zone(v) => emit_u16_be(buffer, *v).unwrap(),
+        }
+    }
+    fn is_nested(&self) -> bool {
+        matches!(self, Tuple::Ip(_) | Tuple::Proto(_))
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for Tuple {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_TUPLE_IP => Tuple::Ip(parse_nlas(payload, "failed to parse CTA_TUPLE_IP")?),
+            CTA_TUPLE_PROTO => {
+                Tuple::Proto(parse_nlas(payload, "failed to parse CTA_TUPLE_PROTO")?)
+            }
+            CTA_TUPLE_ZONE => {
+                Tuple::Zone(read_int!(be u16, payload).context("invalid CTA_TUPLE_ZONE value")?)
+            }
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
+
+// The flattened view of a `CTA_TUPLE_*` block. Ports are `None` for
+// protocols that don't have any (e.g. ICMP), whose id, type and code are
+// left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FiveTuple {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub protocol: u8,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+}
+
+impl FiveTuple {
+    // Returns `None` if the addresses or the protocol number are missing.
+    pub fn from_tuples(tuples: &[Tuple]) -> Option<Self> {
+        let (mut src, mut dst, mut protocol) = (None, None, None);
+        let (mut src_port, mut dst_port) = (None, None);
+        for tuple in tuples {
+            match tuple {
+                Tuple::Ip(ips) => {
+                    for ip in ips {
+                        match ip {
+                            IPTuple::SourceAddress(addr) => src = Some(*addr),
+                            IPTuple::DestinationAddress(addr) => dst = Some(*addr),
+                        }
+                    }
+                }
+                Tuple::Proto(protos) => {
+                    for proto in protos {
+                        match proto {
+                            ProtoTuple::Protocol(v) => protocol = Some(*v),
+                            ProtoTuple::SourcePort(v) => src_port = Some(*v),
+                            ProtoTuple::DestinationPort(v) => dst_port = Some(*v),
+                            ProtoTuple::IcmpId(_)
+                            | ProtoTuple::IcmpType(_)
+                            | ProtoTuple::IcmpCode(_) => {}
+                        }
+                    }
+                }
+                Tuple::Zone(_) => {}
+            }
+        }
+
+        Some(FiveTuple {
+            src: src?,
+            dst: dst?,
+            protocol: protocol?,
+            src_port,
+            dst_port,
+        })
+    }
+
+    // GRE has no ports, ctnetlink carries the 16-bit keys (the PPTP call ids)
+    // in CTA_PROTO_SRC_PORT/CTA_PROTO_DST_PORT instead. Returns them as
+    // `(src, dst)` for a GRE tuple.
+    pub fn gre_keys(&self) -> Option<(u16, u16)> {
+        if self.protocol != IPPROTO_GRE {
+            return None;
+        }
+        Some((self.src_port?, self.dst_port?))
+    }
+
+    // The tuple of the answers to this one when no NAT is involved: addresses
+    // and ports swapped.
+    pub fn reply(&self) -> FiveTuple {
+        FiveTuple {
+            src: self.dst,
+            dst: self.src,
+            protocol: self.protocol,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+        }
+    }
+
+    // The nested `CTA_TUPLE_IP`/`CTA_TUPLE_PROTO` blocks describing this tuple.
+    pub fn to_tuples(&self) -> Vec<Tuple> {
+        let ip = vec![
+            IPTuple::SourceAddress(self.src),
+            IPTuple::DestinationAddress(self.dst),
+        ];
+        let proto = [
+            Some(ProtoTuple::Protocol(self.protocol)),
+            self.src_port.map(ProtoTuple::SourcePort),
+            self.dst_port.map(ProtoTuple::DestinationPort),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        vec![Tuple::Ip(ip), Tuple::Proto(proto)]
+    }
+}
+
+// The `conntrack -L` way of writing a tuple: `src=... dst=... sport=... dport=...`.
+impl fmt::Display for FiveTuple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "src={} dst={}", self.src, self.dst)?;
+        if let Some(port) = self.src_port {
+            write!(f, " sport={}", port)?;
+        }
+        if let Some(port) = self.dst_port {
+            write!(f, " dport={}", port)?;
+        }
+        Ok(())
+    }
+}
+
+// Which side of the connection a tuple describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Original,
+    Reply,
+}
+
+impl Direction {
+    // The attribute kind carrying the tuple for this direction.
+    pub fn tuple_kind(self) -> u16 {
+        match self {
+            Direction::Original => CTA_TUPLE_ORIG,
+            Direction::Reply => CTA_TUPLE_REPLY,
+        }
+    }
+}
+
+// Build the `CTA_TUPLE_ORIG` or `CTA_TUPLE_REPLY` attribute for `tuple`.
+pub fn tuple_for(direction: Direction, tuple: &FiveTuple) -> ConntrackAttribute {
+    match direction {
+        Direction::Original => ConntrackAttribute::CtaTupleOrig(tuple.to_tuples()),
+        Direction::Reply => ConntrackAttribute::CtaTupleReply(tuple.to_tuples()),
+    }
+}
+// Index dumped entries by their original tuple. When two entries share one,
+// the last wins. Entries without a complete original tuple are dropped.
+pub fn index_by_tuple(entries: Vec<NetfilterMessage>) -> HashMap<FiveTuple, NetfilterMessage> {
+    entries
+        .into_iter()
+        .filter_map(|entry| Some((entry.orig_tuple()?, entry)))
+        .collect()
+}
+
+// Which packets of a connection a zone applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoneDir {
+    Orig,
+    Reply,
+    // Both directions.
+    Default,
+}
+
+// A conntrack zone. The kernel only sends one of CTA_ZONE (both directions)
+// or a CTA_TUPLE_ZONE inside the tuple of the direction it applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Zone {
+    pub id: u16,
+    pub dir: ZoneDir,
+}
+
+// -----------Tuple stuff ends-----------------------
+
+// -----------IPTuple stuff starts-----------------------
+#[derive(PartialEq, Debug, Clone)]
+pub enum IPTuple {
+    SourceAddress(IpAddr),
+    DestinationAddress(IpAddr),
+}
+
+// Constants for implementing the Nla trait
+pub const IPV4_LEN: usize = 4;
+pub const IPV6_LEN: usize = 16;
+
+pub const CTA_IP_V4_SRC: u16 = 1;
+pub const CTA_IP_V6_SRC: u16 = 3;
+pub const CTA_IP_V4_DST: u16 = 2;
+pub const CTA_IP_V6_DST: u16 = 4;
+// Helper function needed for implementing the Nla trait
+pub fn emit_ip(addr: &IpAddr, buf: &mut [u8]) {
+    match addr {
+        IpAddr::V4(ip) => {
+            buf[..IPV4_LEN].copy_from_slice(ip.octets().as_slice());
+        }
+        IpAddr::V6(ip) => {
+            buf[..IPV6_LEN].copy_from_slice(ip.octets().as_slice());
+        }
+    }
+}
+
+impl Nla for IPTuple {
+    fn value_len(&self) -> usize {
+        match self {
+            IPTuple::SourceAddress(addr) => match *addr {
+                IpAddr::V4(_) => IPV4_LEN,
+                IpAddr::V6(_) => IPV6_LEN,
+            },
+            IPTuple::DestinationAddress(addr) => match *addr {
+                IpAddr::V4(_) => IPV4_LEN,
+                IpAddr::V6(_) => IPV6_LEN,
+            },
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            IPTuple::SourceAddress(addr) => match *addr {
+                IpAddr::V4(_) => CTA_IP_V4_SRC,
+                IpAddr::V6(_) => CTA_IP_V6_SRC,
+            },
+            IPTuple::DestinationAddress(addr) => match *addr {
+                IpAddr::V4(_) => CTA_IP_V4_DST,
+                IpAddr::V6(_) => CTA_IP_V6_DST,
+            },
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            IPTuple::SourceAddress(addr) => emit_ip(addr, buffer),
+            IPTuple::DestinationAddress(addr) => emit_ip(addr, buffer),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for IPTuple {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        // `parse_ip()` takes whatever length it is given, make sure a
+        // CTA_IP_V6_* really holds 16 bytes and not an IPv4 address. The
+        // tuple only carries the address: no flow label, traffic class or
+        // scope id, so a link-local address comes out without its interface.
+        let expected_len = match buf.kind() {
+            CTA_IP_V4_SRC | CTA_IP_V4_DST => IPV4_LEN,
+            CTA_IP_V6_SRC | CTA_IP_V6_DST => IPV6_LEN,
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        };
+        if payload.len() != expected_len {
+            return Err(DecodeError::from(format!(
+                "invalid address length {} for NLA kind {}",
+                payload.len(),
+                buf.kind()
+            )));
+        }
+
+        Ok(match buf.kind() {
+            CTA_IP_V4_SRC | CTA_IP_V6_SRC => {
+                Self::SourceAddress(parse_ip(payload).context("invalid SourceAddress value")?)
+            }
+            _ => Self::DestinationAddress(
+                parse_ip(payload).context("invalid DestinationAddress value")?,
+            ),
+        })
+    }
+}
+// -----------IPTuple stuff ends-----------------------
+
+// -----------ProtoTuple stuff starts-----------------------
+#[derive(PartialEq, Debug, Clone)]
+pub enum ProtoTuple {
+    Protocol(u8),
+    // Ports are in network byte order on the wire (`nla_put_be16`).
+    SourcePort(u16),
+    DestinationPort(u16),
+    // ICMP has no ports, an echo request and its reply are told apart by
+    // their id instead, big-endian too.
+    IcmpId(u16),
+    IcmpType(u8),
+    IcmpCode(u8),
+}
+pub const CTA_PROTO_NUM: u16 = 1;
+pub const CTA_PROTO_SRC_PORT: u16 = 2;
+pub const CTA_PROTO_DST_PORT: u16 = 3;
+pub const CTA_PROTO_ICMP_ID: u16 = 4;
+pub const CTA_PROTO_ICMP_TYPE: u16 = 5;
+pub const CTA_PROTO_ICMP_CODE: u16 = 6;
+
+impl Nla for ProtoTuple {
+    fn value_len(&self) -> usize {
+        match self {
+            ProtoTuple::Protocol(v) => size_of_val(v),
+            ProtoTuple::SourcePort(v) => size_of_val(v),
+            ProtoTuple::DestinationPort(v) => size_of_val(v),
+            ProtoTuple::IcmpId(v) => size_of_val(v),
+            ProtoTuple::IcmpType(v) => size_of_val(v),
+            ProtoTuple::IcmpCode(v) => size_of_val(v),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            ProtoTuple::Protocol(_) => CTA_PROTO_NUM,
+            ProtoTuple::SourcePort(_) => CTA_PROTO_SRC_PORT,
+            ProtoTuple::DestinationPort(_) => CTA_PROTO_DST_PORT,
+            ProtoTuple::IcmpId(_) => CTA_PROTO_ICMP_ID,
+            ProtoTuple::IcmpType(_) => CTA_PROTO_ICMP_TYPE,
+            ProtoTuple::IcmpCode(_) => CTA_PROTO_ICMP_CODE,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            ProtoTuple::Protocol(v) => buffer[0] = *v,
+            ProtoTuple::SourcePort(v) => emit_u16_be(buffer, *v).unwrap(),
+            ProtoTuple::DestinationPort(v) => emit_u16_be(buffer, *v).unwrap(),
+            ProtoTuple::IcmpId(v) => emit_u16_be(buffer, *v).unwrap(),
+            ProtoTuple::IcmpType(v) | ProtoTuple::IcmpCode(v) => buffer[0] = *v,
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for ProtoTuple {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_PROTO_NUM => {
+                ProtoTuple::Protocol(read_int!(u8, payload).context("invalid CTA_PROTO_NUM value")?)
+            }
+            CTA_PROTO_SRC_PORT => ProtoTuple::SourcePort(
+                read_int!(be u16, payload).context("invalid CTA_PROTO_SRC_PORT value")?,
+            ),
+            CTA_PROTO_DST_PORT => ProtoTuple::DestinationPort(
+                read_int!(be u16, payload).context("invalid CTA_PROTO_DST_PORT value")?,
+            ),
+            CTA_PROTO_ICMP_ID => ProtoTuple::IcmpId(
+                read_int!(be u16, payload).context("invalid CTA_PROTO_ICMP_ID value")?,
+            ),
+            CTA_PROTO_ICMP_TYPE => ProtoTuple::IcmpType(
+                read_int!(u8, payload).context("invalid CTA_PROTO_ICMP_TYPE value")?,
+            ),
+            CTA_PROTO_ICMP_CODE => ProtoTuple::IcmpCode(
+                read_int!(u8, payload).context("invalid CTA_PROTO_ICMP_CODE value")?,
+            ),
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
+
+pub const IPPROTO_ICMP: u8 = 1;
+pub const IPPROTO_TCP: u8 = 6;
+pub const IPPROTO_UDP: u8 = 17;
+pub const IPPROTO_DCCP: u8 = 33;
+pub const IPPROTO_GRE: u8 = 47;
+pub const IPPROTO_ICMPV6: u8 = 58;
+pub const IPPROTO_SCTP: u8 = 132;
+
+// The value of CTA_PROTO_NUM. Anything the crate has no tuple handling for
+// (GRE, ESP...) is kept as `Other`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Icmp,
+    Tcp,
+    Udp,
+    Dccp,
+    Icmpv6,
+    Sctp,
+    Other(u8),
+}
+
+impl From<u8> for Protocol {
+    fn from(v: u8) -> Self {
+        match v {
+            IPPROTO_ICMP => Protocol::Icmp,
+            IPPROTO_TCP => Protocol::Tcp,
+            IPPROTO_UDP => Protocol::Udp,
+            IPPROTO_DCCP => Protocol::Dccp,
+            IPPROTO_ICMPV6 => Protocol::Icmpv6,
+            IPPROTO_SCTP => Protocol::Sctp,
+            other => Protocol::Other(other),
+        }
+    }
+}
+
+impl From<Protocol> for u8 {
+    fn from(protocol: Protocol) -> u8 {
+        match protocol {
+            Protocol::Icmp => IPPROTO_ICMP,
+            Protocol::Tcp => IPPROTO_TCP,
+            Protocol::Udp => IPPROTO_UDP,
+            Protocol::Dccp => IPPROTO_DCCP,
+            Protocol::Icmpv6 => IPPROTO_ICMPV6,
+            Protocol::Sctp => IPPROTO_SCTP,
+            Protocol::Other(v) => v,
+        }
+    }
+}
+
+impl Protocol {
+    // Whether the tuples of this protocol are fully modelled.
+    pub fn is_supported(self) -> bool {
+        !matches!(self, Protocol::Other(_))
+    }
+
+    // Whether CTA_PROTO_SRC_PORT/CTA_PROTO_DST_PORT hold ports.
+    pub fn has_ports(self) -> bool {
+        matches!(
+            self,
+            Protocol::Tcp | Protocol::Udp | Protocol::Dccp | Protocol::Sctp
+        )
+    }
+}
+
+// Name of a CTA_PROTO_NUM, as printed by `conntrack` and accepted by
+// `iptables -p`. `None` for the protocols without a well-known name.
+pub fn protocol_name(protocol: u8) -> Option<&'static str> {
+    match protocol {
+        IPPROTO_ICMP => Some("icmp"),
+        IPPROTO_TCP => Some("tcp"),
+        IPPROTO_UDP => Some("udp"),
+        IPPROTO_DCCP => Some("dccp"),
+        IPPROTO_GRE => Some("gre"),
+        IPPROTO_ICMPV6 => Some("icmpv6"),
+        IPPROTO_SCTP => Some("sctp"),
+        _ => None,
+    }
+}
+
+impl ProtoTuple {
+    // The typed CTA_PROTO_NUM, `None` for the other attributes.
+    pub fn protocol(&self) -> Option<Protocol> {
+        match self {
+            ProtoTuple::Protocol(v) => Some(Protocol::from(*v)),
+            _ => None,
+        }
+    }
+}
+
+// Lets the attributes of an entry be emitted from borrowed slices, e.g.
+// `&[&ProtoTuple]` picked out of several entries.
+impl_nla_for_ref!(ConntrackAttribute, Tuple, IPTuple, ProtoTuple);
+// -----------ProtoTuple stuff ends-----------------------
+
+// -----------Filter stuff starts---------------------
+// CTA_FILTER tells the kernel which fields of the CTA_TUPLE_ORIG/REPLY sent
+// along with a dump have to match. Needs Linux 5.8 or later, older kernels
+// ignore the tuples of a dump and only filter on mark and family.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Filter {
+    OrigFlags(u32),
+    ReplyFlags(u32),
+}
+pub const CTA_FILTER_ORIG_FLAGS: u16 = 1;
+pub const CTA_FILTER_REPLY_FLAGS: u16 = 2;
+
+// Bits of CTA_FILTER_ORIG_FLAGS/CTA_FILTER_REPLY_FLAGS
+pub const CTA_FILTER_FLAG_CTA_IP_SRC: u32 = 1 << 0;
+pub const CTA_FILTER_FLAG_CTA_IP_DST: u32 = 1 << 1;
+pub const CTA_FILTER_FLAG_CTA_TUPLE_ZONE: u32 = 1 << 2;
+pub const CTA_FILTER_FLAG_CTA_PROTO_NUM: u32 = 1 << 3;
+pub const CTA_FILTER_FLAG_CTA_PROTO_SRC_PORT: u32 = 1 << 4;
+pub const CTA_FILTER_FLAG_CTA_PROTO_DST_PORT: u32 = 1 << 5;
+
+impl Nla for Filter {
+    fn value_len(&self) -> usize {
+        match self {
+            Filter::OrigFlags(v) | Filter::ReplyFlags(v) => size_of_val(v),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Filter::OrigFlags(_) => CTA_FILTER_ORIG_FLAGS,
+            Filter::ReplyFlags(_) => CTA_FILTER_REPLY_FLAGS,
+        }
+    }
+
+    // Unlike most of ctnetlink, the flags are in host byte order (unless the
+    // sender marks them NLA_F_NET_BYTEORDER, which parse() honours).
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Filter::OrigFlags(v) | Filter::ReplyFlags(v) => emit_u32(buffer, *v).unwrap(),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for Filter {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        Ok(match buf.kind() {
+            CTA_FILTER_ORIG_FLAGS => Filter::OrigFlags(
+                parse_u32_flagged(buf).context("invalid CTA_FILTER_ORIG_FLAGS value")?,
+            ),
+            CTA_FILTER_REPLY_FLAGS => Filter::ReplyFlags(
+                parse_u32_flagged(buf).context("invalid CTA_FILTER_REPLY_FLAGS value")?,
+            ),
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
+// -----------Filter stuff ends---------------------
+
+// -----------Counter stuff starts---------------------
+// CTA_COUNTERS_ORIG/REPLY, only sent when nf_conntrack_acct is enabled.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Counter {
+    Packets(u64),
+    Bytes(u64),
+}
+pub const CTA_COUNTERS_PACKETS: u16 = 1;
+pub const CTA_COUNTERS_BYTES: u16 = 2;
+pub const CTA_COUNTERS_PAD: u16 = 5;
+
+impl Nla for Counter {
+    fn value_len(&self) -> usize {
+        match self {
+            Counter::Packets(v) | Counter::Bytes(v) => size_of_val(v),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Counter::Packets(_) => CTA_COUNTERS_PACKETS,
+            Counter::Bytes(_) => CTA_COUNTERS_BYTES,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Counter::Packets(v) | Counter::Bytes(v) => emit_u64_be(buffer, *v).unwrap(),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for Counter {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_COUNTERS_PACKETS => Counter::Packets(
+                read_int!(be u64, payload).context("invalid CTA_COUNTERS_PACKETS value")?,
+            ),
+            CTA_COUNTERS_BYTES => Counter::Bytes(
+                read_int!(be u64, payload).context("invalid CTA_COUNTERS_BYTES value")?,
+            ),
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
+
+// The packet and byte counts of one direction of an entry.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct ConntrackCounters {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl ConntrackCounters {
+    pub fn from_counters(counters: &[Counter]) -> Self {
+        let mut out = ConntrackCounters::default();
+        for counter in counters {
+            match counter {
+                Counter::Packets(v) => out.packets = *v,
+                Counter::Bytes(v) => out.bytes = *v,
+            }
+        }
+        out
+    }
+
+    // Packets and bytes per second since the `previous` observation of the
+    // same entry, or `None` if no time `elapsed` since then. A counter
+    // smaller than before was reset (the entry got zeroed with
+    // IPCTNL_MSG_CT_GET_CTRZERO or replaced), its new value is then all that
+    // was counted and is returned as is.
+    pub fn rate(&self, previous: &ConntrackCounters, elapsed: Duration) -> Option<(f64, f64)> {
+        if elapsed.is_zero() {
+            return None;
+        }
+        let delta = |new: u64, old: u64| {
+            if new < old {
+                new as f64
+            } else {
+                (new - old) as f64 / elapsed.as_secs_f64()
+            }
+        };
+        Some((
+            delta(self.packets, previous.packets),
+            delta(self.bytes, previous.bytes),
+        ))
+    }
+}
+// -----------Counter stuff ends---------------------
+
+// -----------Timestamp stuff starts---------------------
+// CTA_TIMESTAMP, only sent when nf_conntrack_timestamp is enabled. Both
+// values are nanoseconds since the epoch. A live entry only has a start.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Timestamp {
+    Start(u64),
+    Stop(u64),
+}
+pub const CTA_TIMESTAMP_START: u16 = 1;
+pub const CTA_TIMESTAMP_STOP: u16 = 2;
+pub const CTA_TIMESTAMP_PAD: u16 = 3;
+
+impl Nla for Timestamp {
+    fn value_len(&self) -> usize {
+        match self {
+            Timestamp::Start(v) | Timestamp::Stop(v) => size_of_val(v),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Timestamp::Start(_) => CTA_TIMESTAMP_START,
+            Timestamp::Stop(_) => CTA_TIMESTAMP_STOP,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Timestamp::Start(v) | Timestamp::Stop(v) => emit_u64_be(buffer, *v).unwrap(),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for Timestamp {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_TIMESTAMP_START => Timestamp::Start(
+                read_int!(be u64, payload).context("invalid CTA_TIMESTAMP_START value")?,
+            ),
+            CTA_TIMESTAMP_STOP => Timestamp::Stop(
+                read_int!(be u64, payload).context("invalid CTA_TIMESTAMP_STOP value")?,
+            ),
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
+
+// Returned by `Timestamp::from_system_time()` for a time that has no u64
+// nanosecond count since the epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampOutOfRange(pub SystemTime);
+
+impl std::error::Error for TimestampOutOfRange {}
+
+impl fmt::Display for TimestampOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} does not fit in a CTA_TIMESTAMP nanosecond count",
+            self.0
+        )
+    }
+}
+
+impl Timestamp {
+    // The value of a CTA_TIMESTAMP_START/STOP for `time`. Times before the
+    // epoch or past 2554 are refused rather than wrapped.
+    pub fn from_system_time(time: SystemTime) -> Result<u64, TimestampOutOfRange> {
+        time.duration_since(UNIX_EPOCH)
+            .ok()
+            .and_then(|since| u64::try_from(since.as_nanos()).ok())
+            .ok_or(TimestampOutOfRange(time))
+    }
+}
+// -----------Timestamp stuff ends---------------------
+
+// -----------Nat stuff starts---------------------
+// CTA_NAT_SRC/CTA_NAT_DST, only found in requests creating an entry: the
+// range the kernel picks the translated address, and port, from. The kernel
+// never dumps them, NATed entries show it in their reply tuple instead.
+#[derive(PartialEq, Debug, Clone)]
+// The address variants only take addresses of their own family, see
+// `NetfilterMessage::validate()`.
+pub enum Nat {
+    V4MinIp(IpAddr),
+    V4MaxIp(IpAddr),
+    Proto(Vec<ProtoNat>),
+    V6MinIp(IpAddr),
+    V6MaxIp(IpAddr),
+}
+pub const CTA_NAT_V4_MINIP: u16 = 1;
+pub const CTA_NAT_V4_MAXIP: u16 = 2;
+pub const CTA_NAT_PROTO: u16 = 3;
+pub const CTA_NAT_V6_MINIP: u16 = 4;
+pub const CTA_NAT_V6_MAXIP: u16 = 5;
+
+impl Nla for Nat {
+    fn value_len(&self) -> usize {
+        match self {
+            Nat::V4MinIp(addr) | Nat::V4MaxIp(addr) | Nat::V6MinIp(addr) | Nat::V6MaxIp(addr) => {
+                match addr {
+                    IpAddr::V4(_) => IPV4_LEN,
+                    IpAddr::V6(_) => IPV6_LEN,
+                }
+            }
+            Nat::Proto(nlas) => attributes_len(nlas),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Nat::V4MinIp(_) => CTA_NAT_V4_MINIP,
+            Nat::V4MaxIp(_) => CTA_NAT_V4_MAXIP,
+            Nat::Proto(_) => CTA_NAT_PROTO,
+            Nat::V6MinIp(_) => CTA_NAT_V6_MINIP,
+            Nat::V6MaxIp(_) => CTA_NAT_V6_MAXIP,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Nat::V4MinIp(addr) | Nat::V4MaxIp(addr) | Nat::V6MinIp(addr) | Nat::V6MaxIp(addr) => {
+                emit_ip(addr, buffer)
+            }
+            Nat::Proto(nlas) => nlas.as_slice().emit(buffer),
+        }
+    }
+
+    fn is_nested(&self) -> bool {
+        matches!(self, Nat::Proto(_))
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for Nat {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        let (variant, expected_len): (fn(IpAddr) -> Self, _) = match buf.kind() {
+            CTA_NAT_V4_MINIP => (Nat::V4MinIp, IPV4_LEN),
+            CTA_NAT_V4_MAXIP => (Nat::V4MaxIp, IPV4_LEN),
+            CTA_NAT_V6_MINIP => (Nat::V6MinIp, IPV6_LEN),
+            CTA_NAT_V6_MAXIP => (Nat::V6MaxIp, IPV6_LEN),
+            CTA_NAT_PROTO => {
+                return Ok(Nat::Proto(parse_nlas(
+                    payload,
+                    "failed to parse CTA_NAT_PROTO",
+                )?));
+            }
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        };
+        if payload.len() != expected_len {
+            return Err(DecodeError::from(format!(
+                "invalid address length {} for NLA kind {}",
+                payload.len(),
+                buf.kind()
+            )));
+        }
+        Ok(variant(
+            parse_ip(payload).context("invalid CTA_NAT address")?,
+        ))
+    }
+}
+
+// CTA_NAT_PROTO, the range of the translated port.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ProtoNat {
+    PortMin(u16),
+    PortMax(u16),
+}
+pub const CTA_PROTONAT_PORT_MIN: u16 = 1;
+pub const CTA_PROTONAT_PORT_MAX: u16 = 2;
+
+impl Nla for ProtoNat {
+    fn value_len(&self) -> usize {
+        match self {
+            ProtoNat::PortMin(v) | ProtoNat::PortMax(v) => size_of_val(v),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            ProtoNat::PortMin(_) => CTA_PROTONAT_PORT_MIN,
+            ProtoNat::PortMax(_) => CTA_PROTONAT_PORT_MAX,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            ProtoNat::PortMin(v) | ProtoNat::PortMax(v) => emit_u16_be(buffer, *v).unwrap(),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for ProtoNat {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_PROTONAT_PORT_MIN => ProtoNat::PortMin(
+                read_int!(be u16, payload).context("invalid CTA_PROTONAT_PORT_MIN value")?,
+            ),
+            CTA_PROTONAT_PORT_MAX => ProtoNat::PortMax(
+                read_int!(be u16, payload).context("invalid CTA_PROTONAT_PORT_MAX value")?,
+            ),
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
+// -----------Nat stuff ends---------------------
+
+// -----------Help stuff starts---------------------
+// CTA_HELP, the connection tracking helper (ALG) of an entry, e.g. "ftp".
+#[derive(PartialEq, Debug, Clone)]
+pub enum Help {
+    Name(String),
+}
+pub const CTA_HELP_NAME: u16 = 1;
+
+impl Nla for Help {
+    fn value_len(&self) -> usize {
+        match self {
+            Help::Name(s) => StringPolicy::NullTerminated.value_len(s),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Help::Name(_) => CTA_HELP_NAME,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Help::Name(s) => StringPolicy::NullTerminated.emit(s, buffer),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for Help {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_HELP_NAME => {
+                Help::Name(parse_string(payload).context("invalid CTA_HELP_NAME value")?)
+            }
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
+// -----------Help stuff ends---------------------
+
+// -----------SecCtx stuff starts---------------------
+// CTA_SECCTX, the security context (e.g. SELinux label) of an entry.
+#[derive(PartialEq, Debug, Clone)]
+pub enum SecCtx {
+    Name(String),
+}
+pub const CTA_SECCTX_NAME: u16 = 1;
+
+impl Nla for SecCtx {
+    fn value_len(&self) -> usize {
+        match self {
+            SecCtx::Name(s) => StringPolicy::NullTerminated.value_len(s),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            SecCtx::Name(_) => CTA_SECCTX_NAME,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            SecCtx::Name(s) => StringPolicy::NullTerminated.emit(s, buffer),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for SecCtx {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            // The attribute length delimits the name, the null the kernel
+            // puts after it may be missing.
+            CTA_SECCTX_NAME => {
+                let name = payload.strip_suffix(&[0]).unwrap_or(payload);
+                SecCtx::Name(
+                    String::from_utf8(name.to_vec()).map_err(|e| {
+                        DecodeError::from(format!("invalid CTA_SECCTX_NAME: {}", e))
+                    })?,
+                )
+            }
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
+// -----------SecCtx stuff ends---------------------
+
+// -----------ProtoInfo stuff starts---------------------
+// UDP keeps no per-flow protocol state, so there is no CTA_PROTOINFO_UDP: a
+// UDP entry simply has no CTA_PROTOINFO.
+pub const CTA_PROTOINFO_TCP: u16 = 1;
+pub const CTA_PROTOINFO_DCCP: u16 = 2;
+pub const CTA_PROTOINFO_SCTP: u16 = 3;
+#[derive(PartialEq, Debug, Clone)]
+pub enum ProtoInfo {
+    TCP(Vec<ProtoInfoTCP>),
+    DCCP(Vec<ProtoInfoDCCP>),
+    SCTP(Vec<ProtoInfoSCTP>),
+}
+impl Nla for ProtoInfo {
+    fn value_len(&self) -> usize {
+        match self {
+            ProtoInfo::TCP(nlas) => attributes_len(nlas),
+            ProtoInfo::DCCP(nlas) => attributes_len(nlas),
+            ProtoInfo::SCTP(nlas) => attributes_len(nlas),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            ProtoInfo::TCP(_) => CTA_PROTOINFO_TCP,
+            ProtoInfo::DCCP(_) => CTA_PROTOINFO_DCCP,
+            ProtoInfo::SCTP(_) => CTA_PROTOINFO_SCTP,
+        }
+    }
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            ProtoInfo::TCP(nlas) => {
+                let mut len = 0;
+                for op in nlas {
+                    op.emit(&mut buffer[len..]);
+                    len += op.buffer_len();
+                }
+            }
+            ProtoInfo::DCCP(nlas) => {
+                let mut len = 0;
+                for op in nlas {
+                    op.emit(&mut buffer[len..]);
+                    len += op.buffer_len();
+                }
+            }
+            ProtoInfo::SCTP(nlas) => {
+                let mut len = 0;
+                for op in nlas {
+                    op.emit(&mut buffer[len..]);
+                    len += op.buffer_len();
+                }
+            }
+        }
+    }
+    fn is_nested(&self) -> bool {
+        matches!(
+            self,
+            ProtoInfo::TCP(_) | ProtoInfo::DCCP(_) | ProtoInfo::SCTP(_)
+        )
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for ProtoInfo {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_PROTOINFO_TCP => {
+                ProtoInfo::TCP(parse_nlas(payload, "failed to parse CTA_PROTOINFO_TCP")?)
+            }
+            CTA_PROTOINFO_DCCP => ProtoInfo::DCCP(parse_nlas_skipping_pad(
+                payload,
+                CTA_PROTOINFO_DCCP_PAD,
+                "failed to parse CTA_PROTOINFO_DCCP",
+            )?),
+            CTA_PROTOINFO_SCTP => {
+                ProtoInfo::SCTP(parse_nlas(payload, "failed to parse CTA_PROTOINFO_SCTP")?)
+            }
+            kind => return Err(DecodeError::from(format!("invalid NLA kind: {}", kind))),
+        })
+    }
+}
+// -----------ProtoInfo stuff ends-----------------------
+
+// -----------ProtoInfoTCP stuff starts---------------------
+pub const CTA_PROTOINFO_TCP_STATE: u16 = 1;
+pub const CTA_PROTOINFO_TCP_WSCALE_ORIGINAL: u16 = 2;
+pub const CTA_PROTOINFO_TCP_WSCALE_REPLY: u16 = 3;
+pub const CTA_PROTOINFO_TCP_FLAGS_ORIGINAL: u16 = 4;
+pub const CTA_PROTOINFO_TCP_FLAGS_REPLY: u16 = 5;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ProtoInfoTCP {
+    State(u8),               // Corresponds to CTA_PROTOINFO_TCP_STATE
+    OriginalWindowScale(u8), // Corresponds to CTA_PROTOINFO_TCP_WSCALE_ORIGINAL
+    ReplyWindowScale(u8),    // Corresponds to CTA_PROTOINFO_TCP_WSCALE_REPLY
+    OriginalFlags(TCPFlags), // Corresponds to CTA_PROTOINFO_TCP_FLAGS_ORIGINAL
+    ReplyFlags(TCPFlags),    // Corresponds to CTA_PROTOINFO_TCP_FLAGS_REPLY
+}
+impl Nla for ProtoInfoTCP {
+    fn value_len(&self) -> usize {
+        match self {
+            ProtoInfoTCP::State(v) => size_of_val(v),
+            ProtoInfoTCP::OriginalWindowScale(v) => size_of_val(v),
+            ProtoInfoTCP::ReplyWindowScale(v) => size_of_val(v),
+            ProtoInfoTCP::OriginalFlags(v) => v.buffer_len(),
+            ProtoInfoTCP::ReplyFlags(v) => v.buffer_len(),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            ProtoInfoTCP::State(_) => CTA_PROTOINFO_TCP_STATE,
+            ProtoInfoTCP::OriginalWindowScale(_) => CTA_PROTOINFO_TCP_WSCALE_ORIGINAL,
+            ProtoInfoTCP::ReplyWindowScale(_) => CTA_PROTOINFO_TCP_WSCALE_REPLY,
+            ProtoInfoTCP::OriginalFlags(_) => CTA_PROTOINFO_TCP_FLAGS_ORIGINAL,
+            ProtoInfoTCP::ReplyFlags(_) => CTA_PROTOINFO_TCP_FLAGS_REPLY,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            ProtoInfoTCP::State(v) => buffer[0] = *v,
+            ProtoInfoTCP::OriginalWindowScale(v) => buffer[0] = *v,
+            ProtoInfoTCP::ReplyWindowScale(v) => buffer[0] = *v,
+            ProtoInfoTCP::OriginalFlags(v) => v.emit(buffer),
+            ProtoInfoTCP::ReplyFlags(v) => v.emit(buffer),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for ProtoInfoTCP {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_PROTOINFO_TCP_STATE => ProtoInfoTCP::State(
+                read_int!(u8, payload).context("invalid CTA_PROTOINFO_TCP_STATE value")?,
+            ),
+            CTA_PROTOINFO_TCP_WSCALE_ORIGINAL => ProtoInfoTCP::OriginalWindowScale(
+                read_int!(u8, payload)
+                    .context("invalid CTA_PROTOINFO_TCP_WSCALE_ORIGINAL value")?,
+            ),
+            CTA_PROTOINFO_TCP_WSCALE_REPLY => ProtoInfoTCP::ReplyWindowScale(
+                read_int!(u8, payload).context("invalid CTA_PROTOINFO_TCP_WSCALE_REPLY value")?,
+            ),
+            CTA_PROTOINFO_TCP_FLAGS_ORIGINAL => ProtoInfoTCP::OriginalFlags(
+                TCPFlagsBuffer::new_checked(payload)
+                    .and_then(|buf| TCPFlags::parse(&buf))
+                    .context("invalid CTA_PROTOINFO_TCP_FLAGS_ORIGINAL value")?,
+            ),
+            CTA_PROTOINFO_TCP_FLAGS_REPLY => ProtoInfoTCP::ReplyFlags(
+                TCPFlagsBuffer::new_checked(payload)
+                    .and_then(|buf| TCPFlags::parse(&buf))
+                    .context("invalid CTA_PROTOINFO_TCP_FLAGS_REPLY value")?,
+            ),
+            kind => {
+                return Err(DecodeError::from(format!(
+                    "invalid ProtoInfoTCP NLA kind: {}",
+                    kind
+                )));
+            }
+        })
+    }
+}
+
+// Values of `enum tcp_conntrack` carried by CTA_PROTOINFO_TCP_STATE
+pub fn tcp_conntrack_state_name(state: u8) -> &'static str {
+    match state {
+        0 => "NONE",
+        1 => "SYN_SENT",
+        2 => "SYN_RECV",
+        3 => "ESTABLISHED",
+        4 => "FIN_WAIT",
+        5 => "CLOSE_WAIT",
+        6 => "LAST_ACK",
+        7 => "TIME_WAIT",
+        8 => "CLOSE",
+        9 => "SYN_SENT2",
+        _ => "UNKNOWN",
+    }
+}
+
+// The symbolic form of CTA_PROTOINFO_TCP_STATE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TcpConnState {
+    None = 0,
+    SynSent = 1,
+    SynRecv = 2,
+    Established = 3,
+    FinWait = 4,
+    CloseWait = 5,
+    LastAck = 6,
+    TimeWait = 7,
+    Close = 8,
+    SynSent2 = 9,
+}
+
+impl TryFrom<u8> for TcpConnState {
+    type Error = DecodeError;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        Ok(match v {
+            0 => Self::None,
+            1 => Self::SynSent,
+            2 => Self::SynRecv,
+            3 => Self::Established,
+            4 => Self::FinWait,
+            5 => Self::CloseWait,
+            6 => Self::LastAck,
+            7 => Self::TimeWait,
+            8 => Self::Close,
+            9 => Self::SynSent2,
+            other => {
+                return Err(DecodeError::from(format!(
+                    "unknown TCP conntrack state: {}",
+                    other
+                )));
+            }
+        })
+    }
+}
+
+impl TcpConnState {
+    // The name `conntrack -L` prints, e.g. `ESTABLISHED`.
+    pub fn name(self) -> &'static str {
+        tcp_conntrack_state_name(self as u8)
+    }
+}
+
+impl fmt::Display for ProtoInfoTCP {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtoInfoTCP::State(v) => write!(f, "state={}", tcp_conntrack_state_name(*v)),
+            ProtoInfoTCP::OriginalWindowScale(v) => write!(f, "wscale_original={}", v),
+            ProtoInfoTCP::ReplyWindowScale(v) => write!(f, "wscale_reply={}", v),
+            ProtoInfoTCP::OriginalFlags(v) => {
+                write!(f, "flags_original={:#04x}/{:#04x}", v.flags, v.mask)
+            }
+            ProtoInfoTCP::ReplyFlags(v) => {
+                write!(f, "flags_reply={:#04x}/{:#04x}", v.flags, v.mask)
+            }
+        }
+    }
+}
+// -----------ProtoInfoTCP stuff ends---------------------
+// -----------TCPFlags stuff starts---------------------
+// Bits of struct nf_ct_tcp_flags
+pub const IP_CT_TCP_FLAG_WINDOW_SCALE: u8 = 1;
+pub const IP_CT_TCP_FLAG_SACK_PERM: u8 = 1 << 1;
+pub const IP_CT_TCP_FLAG_CLOSE_INIT: u8 = 1 << 2;
+pub const IP_CT_TCP_FLAG_BE_LIBERAL: u8 = 1 << 3;
+pub const IP_CT_TCP_FLAG_DATA_UNACKNOWLEDGED: u8 = 1 << 4;
+pub const IP_CT_TCP_FLAG_MAXACK_SET: u8 = 1 << 5;
+pub const IP_CT_TCP_FLAG_CHALLENGE_ACK: u8 = 1 << 6;
+pub const IP_CT_TCP_FLAG_SIMULTANEOUS_OPEN: u8 = 1 << 7;
+
+const TCP_FLAG_NAMES: [(u8, &str); 8] = [
+    (IP_CT_TCP_FLAG_WINDOW_SCALE, "WINDOW_SCALE"),
+    (IP_CT_TCP_FLAG_SACK_PERM, "SACK_PERM"),
+    (IP_CT_TCP_FLAG_CLOSE_INIT, "CLOSE_INIT"),
+    (IP_CT_TCP_FLAG_BE_LIBERAL, "BE_LIBERAL"),
+    (IP_CT_TCP_FLAG_DATA_UNACKNOWLEDGED, "DATA_UNACKNOWLEDGED"),
+    (IP_CT_TCP_FLAG_MAXACK_SET, "MAXACK_SET"),
+    (IP_CT_TCP_FLAG_CHALLENGE_ACK, "CHALLENGE_ACK"),
+    (IP_CT_TCP_FLAG_SIMULTANEOUS_OPEN, "SIMULTANEOUS_OPEN"),
+];
+
+// The flags byte and the mask byte the kernel sends for one direction. On
+// a dump the mask is the flags themselves; on an update it says which of
+// the flags to change.
+#[derive(PartialEq, Clone)]
+pub struct TCPFlags {
+    flags: u8,
+    mask: u8,
+}
+
+impl TCPFlags {
+    pub fn new(flags: u8, mask: u8) -> Self {
+        TCPFlags { flags, mask }
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn mask(&self) -> u8 {
+        self.mask
+    }
+
+    pub fn contains(&self, bits: u8) -> bool {
+        self.flags & bits == bits
+    }
+
+    pub fn window_scale(&self) -> bool {
+        self.contains(IP_CT_TCP_FLAG_WINDOW_SCALE)
+    }
+
+    pub fn sack_perm(&self) -> bool {
+        self.contains(IP_CT_TCP_FLAG_SACK_PERM)
+    }
+
+    pub fn close_init(&self) -> bool {
+        self.contains(IP_CT_TCP_FLAG_CLOSE_INIT)
+    }
+
+    pub fn be_liberal(&self) -> bool {
+        self.contains(IP_CT_TCP_FLAG_BE_LIBERAL)
+    }
+
+    pub fn data_unacknowledged(&self) -> bool {
+        self.contains(IP_CT_TCP_FLAG_DATA_UNACKNOWLEDGED)
+    }
+
+    pub fn maxack_set(&self) -> bool {
+        self.contains(IP_CT_TCP_FLAG_MAXACK_SET)
+    }
+
+    pub fn challenge_ack(&self) -> bool {
+        self.contains(IP_CT_TCP_FLAG_CHALLENGE_ACK)
+    }
+
+    pub fn simultaneous_open(&self) -> bool {
+        self.contains(IP_CT_TCP_FLAG_SIMULTANEOUS_OPEN)
+    }
+}
+
+// Lists the names of the set bits, e.g. `SACK_PERM | BE_LIBERAL`.
+struct TcpFlagNames(u8);
+
+impl fmt::Debug for TcpFlagNames {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = TCP_FLAG_NAMES
+            .iter()
+            .filter(|(bit, _)| self.0 & bit != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        if names.is_empty() {
+            write!(f, "0")
+        } else {
+            write!(f, "{}", names.join(" | "))
+        }
+    }
+}
+
+impl fmt::Debug for TCPFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TCPFlags")
+            .field("flags", &TcpFlagNames(self.flags))
+            .field("mask", &TcpFlagNames(self.mask))
+            .finish()
+    }
+}
+const TCP_FLAGS_LEN: usize = 2;
+buffer!(TCPFlagsBuffer(TCP_FLAGS_LEN) {
+    flags: (u8, 0),
+    mask: (u8, 1),
+});
+impl<T: AsRef<[u8]>> Parseable<TCPFlagsBuffer<T>> for TCPFlags {
+    fn parse(buf: &TCPFlagsBuffer<T>) -> Result<Self, DecodeError> {
+        Ok(TCPFlags {
+            flags: buf.flags(),
+            mask: buf.mask(),
+        })
+    }
+}
+
+impl Emitable for TCPFlags {
+    fn buffer_len(&self) -> usize {
+        TCP_FLAGS_LEN
+    }
+
+    fn emit(&self, buffer: &mut [u8]) {
+        let mut buffer = TCPFlagsBuffer::new(buffer);
+        buffer.set_flags(self.flags);
+        buffer.set_mask(self.mask);
+    }
+}
+// -----------TCPFlags stuff ends---------------------
+
+// -----------ProtoInfoDCCP stuff starts---------------------
+pub const CTA_PROTOINFO_DCCP_STATE: u16 = 1;
+pub const CTA_PROTOINFO_DCCP_ROLE: u16 = 2;
+pub const CTA_PROTOINFO_DCCP_HANDSHAKE_SEQ: u16 = 3;
+pub const CTA_PROTOINFO_DCCP_PAD: u16 = 4;
+
+// The handshake sequence number is sent in network byte order
+// (`nla_put_be64`), possibly behind a CTA_PROTOINFO_DCCP_PAD. The state and
+// the role are single bytes padded to 4 bytes.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ProtoInfoDCCP {
+    State(u8),         // Corresponds to CTA_PROTOINFO_DCCP_STATE
+    Role(u8),          // Corresponds to CTA_PROTOINFO_DCCP_ROLE
+    HandshakeSeq(u64), // Corresponds to CTA_PROTOINFO_DCCP_HANDSHAKE_SEQ
+}
+impl Nla for ProtoInfoDCCP {
+    fn value_len(&self) -> usize {
+        match self {
+            ProtoInfoDCCP::State(v) => size_of_val(v),
+            ProtoInfoDCCP::Role(v) => size_of_val(v),
+            ProtoInfoDCCP::HandshakeSeq(v) => size_of_val(v),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            ProtoInfoDCCP::State(_) => CTA_PROTOINFO_DCCP_STATE,
+            ProtoInfoDCCP::Role(_) => CTA_PROTOINFO_DCCP_ROLE,
+            ProtoInfoDCCP::HandshakeSeq(_) => CTA_PROTOINFO_DCCP_HANDSHAKE_SEQ,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            ProtoInfoDCCP::State(v) => buffer[0] = *v,
+            ProtoInfoDCCP::Role(v) => buffer[0] = *v,
+            ProtoInfoDCCP::HandshakeSeq(v) => emit_u64_be(buffer, *v).unwrap(),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for ProtoInfoDCCP {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_PROTOINFO_DCCP_STATE => ProtoInfoDCCP::State(
+                read_int!(u8, payload).context("invalid CTA_PROTOINFO_DCCP_STATE value")?,
+            ),
+            CTA_PROTOINFO_DCCP_ROLE => ProtoInfoDCCP::Role(
+                read_int!(u8, payload).context("invalid CTA_PROTOINFO_DCCP_ROLE value")?,
+            ),
+            CTA_PROTOINFO_DCCP_HANDSHAKE_SEQ => ProtoInfoDCCP::HandshakeSeq(
+                read_int!(be u64, payload)
+                    .context("invalid CTA_PROTOINFO_DCCP_HANDSHAKE_SEQ value")?,
+            ),
+            kind => {
+                return Err(DecodeError::from(format!(
+                    "invalid ProtoInfoDCCP NLA kind: {}",
+                    kind
+                )));
+            }
+        })
+    }
+}
+// -----------ProtoInfoDCCP stuff ends---------------------
+
+// -----------ProtoInfoSCTP stuff starts---------------------
+pub const CTA_PROTOINFO_SCTP_STATE: u16 = 1;
+pub const CTA_PROTOINFO_SCTP_VTAG_ORIGINAL: u16 = 2;
+pub const CTA_PROTOINFO_SCTP_VTAG_REPLY: u16 = 3;
+
+// The verification tags are sent by the kernel in network byte order
+// (`nla_put_be32`), the state is a single byte padded to 4 bytes.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ProtoInfoSCTP {
+    State(u8),         // Corresponds to CTA_PROTOINFO_SCTP_STATE
+    VTagOriginal(u32), // Corresponds to CTA_PROTOINFO_SCTP_VTAG_ORIGINAL
+    VTagReply(u32),    // Corresponds to CTA_PROTOINFO_SCTP_VTAG_REPLY
+}
+impl Nla for ProtoInfoSCTP {
+    fn value_len(&self) -> usize {
+        match self {
+            ProtoInfoSCTP::State(v) => size_of_val(v),
+            ProtoInfoSCTP::VTagOriginal(v) => size_of_val(v),
+            ProtoInfoSCTP::VTagReply(v) => size_of_val(v),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            ProtoInfoSCTP::State(_) => CTA_PROTOINFO_SCTP_STATE,
+            ProtoInfoSCTP::VTagOriginal(_) => CTA_PROTOINFO_SCTP_VTAG_ORIGINAL,
+            ProtoInfoSCTP::VTagReply(_) => CTA_PROTOINFO_SCTP_VTAG_REPLY,
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            ProtoInfoSCTP::State(v) => buffer[0] = *v,
+            ProtoInfoSCTP::VTagOriginal(v) => emit_u32_be(buffer, *v).unwrap(),
+            ProtoInfoSCTP::VTagReply(v) => emit_u32_be(buffer, *v).unwrap(),
+        }
+    }
+}
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for ProtoInfoSCTP {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+
+        Ok(match buf.kind() {
+            CTA_PROTOINFO_SCTP_STATE => ProtoInfoSCTP::State(
+                read_int!(u8, payload).context("invalid CTA_PROTOINFO_SCTP_STATE value")?,
+            ),
+            CTA_PROTOINFO_SCTP_VTAG_ORIGINAL => ProtoInfoSCTP::VTagOriginal(
+                read_int!(be u32, payload)
+                    .context("invalid CTA_PROTOINFO_SCTP_VTAG_ORIGINAL value")?,
+            ),
+            CTA_PROTOINFO_SCTP_VTAG_REPLY => ProtoInfoSCTP::VTagReply(
+                read_int!(be u32, payload)
+                    .context("invalid CTA_PROTOINFO_SCTP_VTAG_REPLY value")?,
+            ),
+            kind => {
+                return Err(DecodeError::from(format!(
+                    "invalid ProtoInfoSCTP NLA kind: {}",
+                    kind
+                )));
+            }
+        })
+    }
+}
+// -----------ProtoInfoSCTP stuff ends---------------------
+
+pub mod builder;
+pub mod cache;
+pub mod diff;
+pub mod entry;
+pub mod event;
+pub mod expect;
+pub mod rule;
+pub mod socket;
+pub mod stats;
+pub mod tracker;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,22 @@
+use crate::conntrack::{FiveTuple, IPPROTO_GRE, IPPROTO_ICMP, IPPROTO_ICMPV6, protocol_name};
+
+// Turn a connection into the match part of an iptables/ip6tables rule, e.g.
+// `-p tcp -s 10.0.0.1 --sport 1234 -d 10.0.0.2 --dport 443`. ICMP has no
+// ports and GRE keys can't be matched with --sport/--dport, so they are never
+// emitted for those.
+pub fn to_iptables_rule(tuple: &FiveTuple) -> String {
+    let has_ports = !matches!(tuple.protocol, IPPROTO_ICMP | IPPROTO_ICMPV6 | IPPROTO_GRE);
+
+    // `iptables -p` takes the protocol number of the ones without a name.
+    let protocol =
+        protocol_name(tuple.protocol).map_or_else(|| tuple.protocol.to_string(), str::to_string);
+    let mut rule = format!("-p {} -s {}", protocol, tuple.src);
+    if let (true, Some(port)) = (has_ports, tuple.src_port) {
+        rule += &format!(" --sport {}", port);
+    }
+    rule += &format!(" -d {}", tuple.dst);
+    if let (true, Some(port)) = (has_ports, tuple.dst_port) {
+        rule += &format!(" --dport {}", port);
+    }
+    rule
+}
@@ -0,0 +1,238 @@
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::ops::ControlFlow;
+
+use netlink_packet_core::{
+    DecodeError, ErrorMessage, NLM_F_ACK, NLM_F_DUMP, NLM_F_DUMP_FILTERED, NLM_F_REQUEST,
+    NetlinkMessage, NetlinkPayload,
+};
+
+use crate::conntrack::{
+    AF_INET, AF_INET6, ConntrackAttribute, Direction, FiveTuple, NetfilterMessage, Nfgenmsg,
+    conntrack_dump_all_request, tuple_for,
+};
+use crate::for_each_message;
+
+const ENOENT: i32 = 2;
+
+// Anything that can move raw netlink datagrams to and from the kernel. A real
+// `netlink_sys::Socket` implements it behind the `socket` feature, tests use a
+// canned transport instead.
+pub trait NetlinkTransport {
+    fn send(&mut self, buf: &[u8]) -> io::Result<()>;
+
+    // Returns one datagram, which may hold several netlink messages.
+    fn recv(&mut self) -> io::Result<Vec<u8>>;
+}
+
+#[cfg(feature = "socket")]
+impl NetlinkTransport for netlink_sys::Socket {
+    fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        netlink_sys::Socket::send(self, buf, 0).map(|_| ())
+    }
+
+    fn recv(&mut self) -> io::Result<Vec<u8>> {
+        self.recv_from_full().map(|(buf, _)| buf)
+    }
+}
+
+// Everything that can go wrong while talking to the kernel.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Decode(DecodeError),
+    // The kernel answered with a non-zero NLMSG_ERROR.
+    Kernel(ErrorMessage),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "netlink transport error: {}", e),
+            Error::Decode(e) => write!(f, "failed to decode netlink reply: {}", e),
+            Error::Kernel(e) => write!(f, "kernel returned an error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+// The entries of a dump. `filtered` is set when the kernel flagged its
+// replies with NLM_F_DUMP_FILTERED, i.e. it did apply the filter sent along
+// with the request instead of dumping everything.
+#[derive(Debug, Default, PartialEq)]
+pub struct Dump {
+    pub entries: Vec<NetfilterMessage>,
+    pub filtered: bool,
+}
+
+// Dump every conntrack entry whose mark equals `mark`. The filtering is done
+// by the kernel, which only returns entries matching `CTA_MARK`/`CTA_MARK_MASK`.
+pub fn dump_by_mark<S: NetlinkTransport>(socket: &mut S, mark: u32) -> Result<Dump, Error> {
+    let request = NetfilterMessage::ConntrackGet {
+        header: Nfgenmsg {
+            nfgen_family: 0,
+            version: 0,
+            resource_id: 0,
+        },
+        nlas: vec![
+            ConntrackAttribute::CtaMark(mark),
+            ConntrackAttribute::CtaMarkMask(u32::MAX),
+        ],
+    };
+    let mut packet = NetlinkMessage::from(request);
+    packet.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+    packet.finalize();
+
+    send(socket, &packet)?;
+    recv_dump(socket)
+}
+
+// Whether the kernel tracks a connection with `tuple` as its original tuple.
+// The GET is sent with NLM_F_ACK: a known entry comes back followed by an
+// acknowledgement, an unknown one as an ENOENT error.
+pub fn conntrack_exists<S: NetlinkTransport>(
+    socket: &mut S,
+    tuple: &FiveTuple,
+) -> Result<bool, Error> {
+    let request = NetfilterMessage::ConntrackGet {
+        header: Nfgenmsg {
+            nfgen_family: match tuple.src {
+                IpAddr::V4(_) => AF_INET,
+                IpAddr::V6(_) => AF_INET6,
+            },
+            version: 0,
+            resource_id: 0,
+        },
+        nlas: vec![tuple_for(Direction::Original, tuple)],
+    };
+    let mut packet = NetlinkMessage::from(request);
+    packet.header.flags = NLM_F_REQUEST | NLM_F_ACK;
+    packet.finalize();
+
+    send(socket, &packet)?;
+    match recv_ack(socket) {
+        Ok(()) => Ok(true),
+        Err(Error::Kernel(e)) if is_enoent(&e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// Delete every entry whose CTA_STATUS has all the bits of `status_mask` set,
+// e.g. `IPS_ASSURED` to kill the established connections. Each one is
+// deleted by its original tuple and CTA_ID, so that an entry recreated with
+// the same tuple since the dump is left alone. Returns how many were deleted.
+pub fn delete_entries_matching<S: NetlinkTransport>(
+    socket: &mut S,
+    status_mask: u32,
+) -> Result<usize, Error> {
+    send(socket, &conntrack_dump_all_request(0))?;
+    let dump = recv_dump(socket)?;
+
+    let mut deleted = 0;
+    for entry in dump.entries {
+        if !entry
+            .status()
+            .is_some_and(|status| status.contains(status_mask))
+        {
+            continue;
+        }
+        // A DELETE without a tuple flushes the whole table.
+        let Some(tuple) = entry
+            .nlas()
+            .iter()
+            .find(|nla| matches!(nla, ConntrackAttribute::CtaTupleOrig(_)))
+        else {
+            continue;
+        };
+        let mut nlas = vec![tuple.clone()];
+        nlas.extend(entry.id().map(ConntrackAttribute::CtaId));
+
+        let mut packet =
+            NetlinkMessage::from(NetfilterMessage::conntrack_delete(*entry.header(), nlas));
+        packet.header.flags = NLM_F_REQUEST | NLM_F_ACK;
+        packet.finalize();
+
+        send(socket, &packet)?;
+        match recv_ack(socket) {
+            Ok(()) => deleted += 1,
+            // Gone on its own since the dump.
+            Err(Error::Kernel(e)) if is_enoent(&e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(deleted)
+}
+
+fn is_enoent(e: &ErrorMessage) -> bool {
+    e.code.is_some_and(|code| code.get() == -ENOENT)
+}
+
+fn send<S: NetlinkTransport>(
+    socket: &mut S,
+    packet: &NetlinkMessage<NetfilterMessage>,
+) -> Result<(), Error> {
+    let mut buf = vec![0; packet.buffer_len()];
+    packet.serialize(&mut buf);
+    socket.send(&buf)?;
+    Ok(())
+}
+
+// Feed every message the kernel sends back to `handle` until it returns
+// something. A single datagram carries several messages, each one starting
+// at the aligned end of the previous one, see `for_each_message()`.
+fn recv_until<S, T, F>(socket: &mut S, mut handle: F) -> Result<T, Error>
+where
+    S: NetlinkTransport,
+    F: FnMut(NetlinkMessage<NetfilterMessage>) -> Option<Result<T, Error>>,
+{
+    loop {
+        let reply = socket.recv()?;
+        let handled = for_each_message(&reply, |message| match handle(message) {
+            Some(result) => ControlFlow::Break(result),
+            None => ControlFlow::Continue(()),
+        })?;
+        if let Some(result) = handled {
+            return result;
+        }
+    }
+}
+
+// Collect the entries of a dump up to its NLMSG_DONE.
+fn recv_dump<S: NetlinkTransport>(socket: &mut S) -> Result<Dump, Error> {
+    let mut dump = Dump::default();
+    recv_until(socket, |message| {
+        dump.filtered |= message.header.flags & NLM_F_DUMP_FILTERED != 0;
+        match message.payload {
+            NetlinkPayload::InnerMessage(entry) => dump.entries.push(entry),
+            NetlinkPayload::Done(_) => return Some(Ok(())),
+            NetlinkPayload::Error(e) if e.code.is_some() => return Some(Err(Error::Kernel(e))),
+            _ => {}
+        }
+        None
+    })?;
+    Ok(dump)
+}
+
+// Wait for the NLMSG_ERROR answering a request sent with NLM_F_ACK, skipping
+// whatever comes before it.
+fn recv_ack<S: NetlinkTransport>(socket: &mut S) -> Result<(), Error> {
+    recv_until(socket, |message| match message.payload {
+        NetlinkPayload::Error(e) if e.code.is_some() => Some(Err(Error::Kernel(e))),
+        NetlinkPayload::Error(_) => Some(Ok(())),
+        _ => None,
+    })
+}
@@ -0,0 +1,166 @@
+use crate::{attributes_len, emit_header_and_nlas};
+use netlink_packet_core::{
+    DecodeError, DefaultNla, Emitable, ErrorContext, NetlinkDeserializable, NetlinkHeader,
+    NetlinkPayload, NetlinkSerializable, Nla, NlaBuffer, Parseable, emit_u32_be,
+};
+
+use crate::conntrack::{
+    IPCTNL_MSG_CT_GET_STATS_CPU, NFGENMSG_LEN, NFNL_SUBSYS_CTNETLINK, Nfgenmsg, NfgenmsgBuffer,
+    parse_nlas,
+};
+
+pub const NETFILTER_CONNTRACK_GET_STATS_CPU_MESSAGE_TYPE: u16 =
+    NFNL_SUBSYS_CTNETLINK << 8 | IPCTNL_MSG_CT_GET_STATS_CPU;
+
+// `conntrack -S`: the request is a dump, the kernel answers with one message
+// per possible CPU.
+#[derive(PartialEq, Debug)]
+pub struct StatsCpuMessage {
+    pub header: Nfgenmsg,
+    pub nlas: Vec<StatsCpuAttribute>,
+}
+
+impl StatsCpuMessage {
+    // In the replies, `res_id` is the CPU the counters belong to.
+    pub fn cpu_index(&self) -> u16 {
+        self.header.resource_id
+    }
+}
+
+impl NetlinkSerializable for StatsCpuMessage {
+    fn message_type(&self) -> u16 {
+        NETFILTER_CONNTRACK_GET_STATS_CPU_MESSAGE_TYPE
+    }
+
+    fn buffer_len(&self) -> usize {
+        self.header.buffer_len() + attributes_len(&self.nlas)
+    }
+
+    fn serialize(&self, buffer: &mut [u8]) {
+        emit_header_and_nlas(&self.header, &self.nlas, buffer)
+    }
+}
+
+impl NetlinkDeserializable for StatsCpuMessage {
+    type Error = DecodeError;
+
+    fn deserialize(header: &NetlinkHeader, payload: &[u8]) -> Result<Self, Self::Error> {
+        if header.message_type != NETFILTER_CONNTRACK_GET_STATS_CPU_MESSAGE_TYPE {
+            return Err(DecodeError::from(format!(
+                "Unknown message type for conntrack stats: {}",
+                header.message_type
+            )));
+        }
+        if payload.len() < NFGENMSG_LEN {
+            return Err(DecodeError::from(
+                "Payload is too short for NFGENMSG header",
+            ));
+        }
+
+        let nfgen_msg_buf = NfgenmsgBuffer::new(&payload[..NFGENMSG_LEN]);
+        Ok(StatsCpuMessage {
+            header: Nfgenmsg::parse(&nfgen_msg_buf)?,
+            nlas: parse_nlas(
+                &payload[NFGENMSG_LEN..],
+                "failed to parse conntrack stats attributes",
+            )?,
+        })
+    }
+}
+
+impl From<StatsCpuMessage> for NetlinkPayload<StatsCpuMessage> {
+    fn from(message: StatsCpuMessage) -> Self {
+        NetlinkPayload::InnerMessage(message)
+    }
+}
+
+// The per-CPU counters. The kernel stopped filling some of the CTA_STATS_*
+// attributes (searched, new, ignore, delete...), those end up in `Other`.
+#[derive(PartialEq, Debug)]
+pub enum StatsCpuAttribute {
+    Found(u32),
+    Invalid(u32),
+    Insert(u32),
+    InsertFailed(u32),
+    Drop(u32),
+    EarlyDrop(u32),
+    Error(u32),
+    SearchRestart(u32),
+    ClashResolve(u32),
+    ChainTooLong(u32),
+    Other(DefaultNla),
+}
+pub const CTA_STATS_FOUND: u16 = 2;
+pub const CTA_STATS_INVALID: u16 = 4;
+pub const CTA_STATS_INSERT: u16 = 8;
+pub const CTA_STATS_INSERT_FAILED: u16 = 9;
+pub const CTA_STATS_DROP: u16 = 10;
+pub const CTA_STATS_EARLY_DROP: u16 = 11;
+pub const CTA_STATS_ERROR: u16 = 12;
+pub const CTA_STATS_SEARCH_RESTART: u16 = 13;
+pub const CTA_STATS_CLASH_RESOLVE: u16 = 14;
+pub const CTA_STATS_CHAIN_TOOLONG: u16 = 15;
+
+impl Nla for StatsCpuAttribute {
+    fn value_len(&self) -> usize {
+        match self {
+            Self::Other(nla) => nla.value_len(),
+            _ => size_of::<u32>(),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Self::Found(_) => CTA_STATS_FOUND,
+            Self::Invalid(_) => CTA_STATS_INVALID,
+            Self::Insert(_) => CTA_STATS_INSERT,
+            Self::InsertFailed(_) => CTA_STATS_INSERT_FAILED,
+            Self::Drop(_) => CTA_STATS_DROP,
+            Self::EarlyDrop(_) => CTA_STATS_EARLY_DROP,
+            Self::Error(_) => CTA_STATS_ERROR,
+            Self::SearchRestart(_) => CTA_STATS_SEARCH_RESTART,
+            Self::ClashResolve(_) => CTA_STATS_CLASH_RESOLVE,
+            Self::ChainTooLong(_) => CTA_STATS_CHAIN_TOOLONG,
+            Self::Other(nla) => nla.kind(),
+        }
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Self::Found(v)
+            | Self::Invalid(v)
+            | Self::Insert(v)
+            | Self::InsertFailed(v)
+            | Self::Drop(v)
+            | Self::EarlyDrop(v)
+            | Self::Error(v)
+            | Self::SearchRestart(v)
+            | Self::ClashResolve(v)
+            | Self::ChainTooLong(v) => emit_u32_be(buffer, *v).unwrap(),
+            Self::Other(nla) => nla.emit_value(buffer),
+        }
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for StatsCpuAttribute {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let counter = |variant: fn(u32) -> Self| {
+            read_int!(be u32, buf.value())
+                .map(variant)
+                .context(format!("invalid CTA_STATS value for kind {}", buf.kind()))
+        };
+        match buf.kind() {
+            CTA_STATS_FOUND => counter(Self::Found),
+            CTA_STATS_INVALID => counter(Self::Invalid),
+            CTA_STATS_INSERT => counter(Self::Insert),
+            CTA_STATS_INSERT_FAILED => counter(Self::InsertFailed),
+            CTA_STATS_DROP => counter(Self::Drop),
+            CTA_STATS_EARLY_DROP => counter(Self::EarlyDrop),
+            CTA_STATS_ERROR => counter(Self::Error),
+            CTA_STATS_SEARCH_RESTART => counter(Self::SearchRestart),
+            CTA_STATS_CLASH_RESOLVE => counter(Self::ClashResolve),
+            CTA_STATS_CHAIN_TOOLONG => counter(Self::ChainTooLong),
+            _ => Ok(Self::Other(DefaultNla::parse(buf)?)),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use netlink_packet_core::{NLM_F_CREATE, NLM_F_EXCL, NetlinkMessage, NetlinkPayload};
+
+use crate::conntrack::entry::ConntrackEntry;
+use crate::conntrack::event::{DeleteReason, delete_reason};
+use crate::conntrack::{FiveTuple, NetfilterMessage};
+
+// Identifies a connection across its events. The tuple alone is not enough:
+// once an entry is gone, a new connection may reuse the same tuple, but the
+// kernel gives it another CTA_ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionKey {
+    pub tuple: FiveTuple,
+    pub id: Option<u32>,
+}
+
+// What a `ConnectionTracker` reports. Every method does nothing by default,
+// implement the ones you care about.
+pub trait LifecycleHandler {
+    fn opened(&mut self, _key: &ConnectionKey, _entry: &ConntrackEntry) {}
+
+    // The status bits or the protocol state (e.g. SYN_SENT to ESTABLISHED)
+    // changed. An update that only refreshes the timeout is not reported.
+    fn state_changed(
+        &mut self,
+        _key: &ConnectionKey,
+        _old: &ConntrackEntry,
+        _new: &ConntrackEntry,
+    ) {
+    }
+
+    fn closed(&mut self, _key: &ConnectionKey, _entry: &ConntrackEntry, _reason: DeleteReason) {}
+}
+
+// Follows connections through the events of the conntrack multicast groups:
+// NEW opens a connection, UPDATE changes it and DELETE closes it. It does not
+// care where the messages come from, feed it every decoded event in order.
+#[derive(Debug, Default)]
+pub struct ConnectionTracker {
+    connections: HashMap<ConnectionKey, ConntrackEntry>,
+}
+
+impl ConnectionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Update the tracked connections with `message` and tell `handler` about
+    // it. Connections that were already open when the tracker started are
+    // reported as opened on their first UPDATE, and closed even if they were
+    // never seen before. Anything that is not an event about an entry with
+    // an original tuple is ignored.
+    pub fn process<H: LifecycleHandler>(
+        &mut self,
+        message: &NetlinkMessage<NetfilterMessage>,
+        handler: &mut H,
+    ) {
+        let NetlinkPayload::InnerMessage(event) = &message.payload else {
+            return;
+        };
+        let Some(tuple) = event.orig_tuple() else {
+            return;
+        };
+        let key = ConnectionKey {
+            tuple,
+            id: event.id(),
+        };
+
+        match event {
+            NetfilterMessage::ConntrackNew { .. } => {
+                let created = message.header.flags & (NLM_F_CREATE | NLM_F_EXCL) != 0;
+                match self.connections.get_mut(&key) {
+                    Some(entry) if !created => {
+                        let old = entry.clone();
+                        entry.apply_update(event);
+                        if entry.status != old.status || entry.protoinfo != old.protoinfo {
+                            handler.state_changed(&key, &old, entry);
+                        }
+                    }
+                    _ => {
+                        let mut entry = ConntrackEntry::default();
+                        entry.apply_update(event);
+                        handler.opened(&key, &entry);
+                        self.connections.insert(key, entry);
+                    }
+                }
+            }
+            NetfilterMessage::ConntrackDelete { .. } => {
+                let mut entry = self.connections.remove(&key).unwrap_or_default();
+                entry.apply_update(event);
+                handler.closed(&key, &entry, delete_reason(message));
+            }
+            NetfilterMessage::ConntrackGet { .. } | NetfilterMessage::Raw { .. } => {}
+        }
+    }
+
+    // The connection `key`, as of the last event about it.
+    pub fn get(&self, key: &ConnectionKey) -> Option<&ConntrackEntry> {
+        self.connections.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+}
@@ -0,0 +1,433 @@
+// Helpers shared by the example protocols in `src/bin/`. The conntrack one
+// lives in the `conntrack` module so that other crates can use its types.
+
+use netlink_packet_core::{
+    Emitable, ErrorContext, ErrorMessage, NLM_F_ACK_TLVS, NLM_F_CAPPED, NLMSG_NOOP, NetlinkBuffer,
+    NetlinkDeserializable, NetlinkHeader, NetlinkMessage, NetlinkSerializable, NlaBuffer,
+    NlasIterator, Parseable, parse_string, parse_u32,
+};
+// The exported macros below name these through `$crate`, so that crates using
+// them don't need netlink-packet-core as a direct dependency.
+pub use netlink_packet_core::{DecodeError, Nla};
+use std::fmt;
+use std::ops::ControlFlow;
+
+// Number of bytes `nlas` take once emitted, padding included. Handy to size a
+// buffer without building the whole message.
+pub fn attributes_len<A: Nla>(nlas: &[A]) -> usize {
+    nlas.iter().map(|nla| nla.buffer_len()).sum()
+}
+
+// Every message in the examples is a fixed-size protocol header directly
+// followed by its attributes. Write both into `buffer`.
+pub fn emit_header_and_nlas<H: Emitable, A: Nla>(header: &H, nlas: &[A], buffer: &mut [u8]) {
+    let header_len = header.buffer_len();
+    header.emit(&mut buffer[..header_len]);
+    nlas.emit(&mut buffer[header_len..]);
+}
+
+// netlink-packet-core already has `impl<T: Nla> Emitable for T`, so a blanket
+// `Emitable for &T` would conflict with it, and `Nla for &T` is not ours to
+// write for every `T`. Implementing `Nla` on references to a given local type
+// is allowed though, and makes `&[&T]` emittable like `&[T]`.
+#[macro_export]
+macro_rules! impl_nla_for_ref {
+    ($($ty:ty),+ $(,)?) => {
+        $(
+            impl $crate::Nla for &$ty {
+                fn value_len(&self) -> usize {
+                    $crate::Nla::value_len(*self)
+                }
+
+                fn kind(&self) -> u16 {
+                    $crate::Nla::kind(*self)
+                }
+
+                fn emit_value(&self, buffer: &mut [u8]) {
+                    $crate::Nla::emit_value(*self, buffer)
+                }
+
+                fn is_nested(&self) -> bool {
+                    $crate::Nla::is_nested(*self)
+                }
+
+                fn is_network_byteorder(&self) -> bool {
+                    $crate::Nla::is_network_byteorder(*self)
+                }
+            }
+        )+
+    };
+}
+
+// Read an integer attribute value, checking that `payload` is exactly as
+// wide as the type: `read_int!(u32, payload)` for host byte order,
+// `read_int!(be u32, payload)` for network byte order. Every width fails the
+// same way, with "expected N bytes, got M".
+#[macro_export]
+macro_rules! read_int {
+    (@read $ty:ty, $from_bytes:ident, $payload:expr) => {{
+        let payload: &[u8] = $payload;
+        match <[u8; ::std::mem::size_of::<$ty>()]>::try_from(payload) {
+            Ok(bytes) => Ok(<$ty>::$from_bytes(bytes)),
+            Err(_) => Err($crate::DecodeError::from(format!(
+                "expected {} bytes, got {}",
+                ::std::mem::size_of::<$ty>(),
+                payload.len()
+            ))),
+        }
+    }};
+    (be $ty:ty, $payload:expr) => {
+        $crate::read_int!(@read $ty, from_be_bytes, $payload)
+    };
+    ($ty:ty, $payload:expr) => {
+        $crate::read_int!(@read $ty, from_ne_bytes, $payload)
+    };
+}
+
+// Parse the value of `nla` as a host byte order integer, unless its sender
+// set NLA_F_NET_BYTEORDER on it. The kind returned by `NlaBuffer::kind()`
+// already has the flag masked out, so this is the only place it matters.
+// Only CTA_FILTER's flags need it, ports are always big-endian.
+pub fn parse_u32_flagged<T: AsRef<[u8]> + ?Sized>(nla: &NlaBuffer<&T>) -> Result<u32, DecodeError> {
+    if nla.network_byte_order_flag() {
+        read_int!(be u32, nla.value())
+    } else {
+        read_int!(u32, nla.value())
+    }
+}
+
+// Serialize `packet` and decode it again, which is what every example's
+// `main()` does by hand. `packet` must have been finalized.
+pub fn roundtrip<T>(packet: &NetlinkMessage<T>) -> Result<NetlinkMessage<T>, DecodeError>
+where
+    T: NetlinkSerializable + NetlinkDeserializable,
+{
+    let mut buf = vec![0; packet.buffer_len()];
+    packet.serialize(&mut buf);
+    NetlinkMessage::deserialize(&buf)
+}
+
+// Attributes the kernel always emits in the same order. That order rarely
+// follows the kinds, so protocols override `sort_key()` to give each
+// attribute its rank.
+pub trait KernelOrder: Nla {
+    fn sort_key(&self) -> u16 {
+        self.kind()
+    }
+}
+
+// Put `nlas` in the order the kernel would emit them. Attributes sharing a
+// sort key keep their relative order.
+pub fn sort_canonical<A: KernelOrder>(nlas: &mut [A]) {
+    nlas.sort_by_key(|nla| nla.sort_key());
+}
+
+// An attribute that is just a kind and some bytes, to get a new protocol off
+// the ground before writing a proper enum for its attributes. The
+// `NLA_F_NESTED`/`NLA_F_NET_BYTEORDER` flags are not kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyValue {
+    pub kind: u16,
+    pub value: Vec<u8>,
+}
+
+impl Nla for KeyValue {
+    fn value_len(&self) -> usize {
+        self.value.len()
+    }
+
+    fn kind(&self) -> u16 {
+        self.kind
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        buffer[..self.value.len()].copy_from_slice(&self.value);
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for KeyValue {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        Ok(KeyValue {
+            kind: buf.kind(),
+            value: buf.value().to_vec(),
+        })
+    }
+}
+
+// Whether a string attribute ends with a null byte on the wire. Most
+// netlink families expect one (NLA_NUL_STRING, or NLA_STRING read with
+// nla_strscpy()), some length-delimited ones don't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StringPolicy {
+    #[default]
+    NullTerminated,
+    NotTerminated,
+}
+
+impl StringPolicy {
+    pub fn value_len(self, s: &str) -> usize {
+        match self {
+            StringPolicy::NullTerminated => s.len() + 1,
+            StringPolicy::NotTerminated => s.len(),
+        }
+    }
+
+    // `buffer` must be `value_len(s)` bytes long.
+    pub fn emit(self, s: &str, buffer: &mut [u8]) {
+        buffer[..s.len()].copy_from_slice(s.as_bytes());
+        if self == StringPolicy::NullTerminated {
+            buffer[s.len()] = 0;
+        }
+    }
+}
+
+// A string attribute with its own `StringPolicy`. Parsing accepts both forms
+// and always yields the default policy, the wire doesn't say which one was
+// used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NlaString {
+    pub kind: u16,
+    pub value: String,
+    pub policy: StringPolicy,
+}
+
+impl Nla for NlaString {
+    fn value_len(&self) -> usize {
+        self.policy.value_len(&self.value)
+    }
+
+    fn kind(&self) -> u16 {
+        self.kind
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        self.policy.emit(&self.value, buffer)
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for NlaString {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let value = buf.value();
+        let value = value.strip_suffix(&[0]).unwrap_or(value);
+        Ok(NlaString {
+            kind: buf.kind(),
+            value: String::from_utf8(value.to_vec())
+                .map_err(|e| DecodeError::from(format!("invalid string attribute: {}", e)))?,
+            policy: StringPolicy::default(),
+        })
+    }
+}
+
+// `NetlinkHeader` lives in netlink-packet-core, so `peek` has to come from a
+// trait.
+pub trait PeekHeader: Sized {
+    // Parse the fixed 16-byte header at the start of `buf`, without looking
+    // at the payload beyond checking that `length` fits in `buf`.
+    fn peek(buf: &[u8]) -> Result<Self, DecodeError>;
+}
+
+impl PeekHeader for NetlinkHeader {
+    fn peek(buf: &[u8]) -> Result<Self, DecodeError> {
+        NetlinkHeader::parse(&NetlinkBuffer::new_checked(buf)?)
+    }
+}
+
+// A protocol that can be told apart from the others by the
+// `nlmsghdr.message_type` of its messages, so that a single reader can hand
+// every buffer to the right decoder.
+pub trait NetlinkProtocol: Sized {
+    // Whether `message_type` belongs to this protocol. The ranges of the
+    // protocols sharing a reader must not overlap.
+    fn owns_message_type(message_type: u16) -> bool;
+
+    fn deserialize(buf: &[u8]) -> Result<NetlinkMessage<Self>, DecodeError>;
+}
+
+// Decode `buf` as a `P` message if `P` owns its message type. Returns
+// `Ok(None)` when the message belongs to some other protocol.
+pub fn route<P: NetlinkProtocol>(buf: &[u8]) -> Result<Option<NetlinkMessage<P>>, DecodeError> {
+    let header = NetlinkHeader::peek(buf)?;
+    if !P::owns_message_type(header.message_type) {
+        return Ok(None);
+    }
+    P::deserialize(buf).map(Some)
+}
+
+// Cuts a byte stream into netlink messages when the reads don't follow
+// message boundaries, e.g. dump pages read into a fixed-size buffer. A
+// message cut short, even in the middle of an attribute, stays buffered until
+// the rest of it is pushed.
+#[derive(Debug, Default)]
+pub struct NetlinkStreamReader {
+    buf: Vec<u8>,
+    // Padding of the last message returned that was not received with it,
+    // dropped from the start of the next push.
+    padding_owed: usize,
+}
+
+impl NetlinkStreamReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        let padding = self.padding_owed.min(bytes.len());
+        self.padding_owed -= padding;
+        self.buf.extend_from_slice(&bytes[padding..]);
+    }
+
+    // Bytes received but not decoded yet.
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    // Decode the next message, or return `Ok(None)` if it hasn't been
+    // received in full yet.
+    pub fn next_message<T: NetlinkDeserializable>(
+        &mut self,
+    ) -> Result<Option<NetlinkMessage<T>>, DecodeError> {
+        match self.next_raw()? {
+            Some(raw) => NetlinkMessage::deserialize(&raw).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // Like `next_message()`, without decoding the message, e.g. to hand it
+    // to `route()`. NLMSG_NOOP messages carry nothing and are skipped. A
+    // length shorter than the header leaves no way to find where the next
+    // message starts: everything buffered is dropped along with the error,
+    // and the reader starts over with the next push.
+    pub fn next_raw(&mut self) -> Result<Option<Vec<u8>>, DecodeError> {
+        loop {
+            if self.buf.len() < NETLINK_HEADER_LEN {
+                return Ok(None);
+            }
+            let length = NetlinkBuffer::new(&self.buf).length() as usize;
+            if length < NETLINK_HEADER_LEN {
+                self.buf.clear();
+                self.padding_owed = 0;
+                return Err(DecodeError::from(format!(
+                    "invalid netlink message length: {}",
+                    length
+                )));
+            }
+            if self.buf.len() < length {
+                return Ok(None);
+            }
+
+            let raw = self.buf[..length].to_vec();
+            // The padding may only come with the next read.
+            let aligned = (length + 3) & !3;
+            let consumed = aligned.min(self.buf.len());
+            self.padding_owed = aligned - consumed;
+            self.buf.drain(..consumed);
+            if NetlinkBuffer::new(&raw).message_type() != NLMSG_NOOP {
+                return Ok(Some(raw));
+            }
+        }
+    }
+}
+
+// Decode the messages packed in `buf`, e.g. a datagram, in order, and hand
+// them to `handle` until it breaks with something. Whatever follows is not
+// decoded. Fails if `buf` ends in the middle of a message.
+pub fn for_each_message<T, B>(
+    buf: &[u8],
+    mut handle: impl FnMut(NetlinkMessage<T>) -> ControlFlow<B>,
+) -> Result<Option<B>, DecodeError>
+where
+    T: NetlinkDeserializable,
+{
+    let mut stream = NetlinkStreamReader::new();
+    stream.push(buf);
+    while let Some(message) = stream.next_message()? {
+        if let ControlFlow::Break(result) = handle(message) {
+            return Ok(Some(result));
+        }
+    }
+    if stream.pending() != 0 {
+        return Err(DecodeError::from(format!(
+            "netlink message cut short, {} bytes left",
+            stream.pending()
+        )));
+    }
+    Ok(None)
+}
+
+// Attributes of an extended ack, see `ExtAck`.
+pub const NLMSGERR_ATTR_MSG: u16 = 1;
+pub const NLMSGERR_ATTR_OFFS: u16 = 2;
+
+const NETLINK_HEADER_LEN: usize = 16;
+
+// What the kernel has to say about an error besides its errno, e.g.
+// `Unknown conntrack family`. Sockets with NETLINK_EXT_ACK set get these as
+// NLMSGERR_ATTR_* attributes after the echoed request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtAck {
+    pub message: Option<String>,
+    // Offset in the echoed request of the attribute that was rejected.
+    pub offset: Option<u32>,
+}
+
+impl ExtAck {
+    // `flags` are the ones of the NLMSG_ERROR header `error` came with.
+    // Returns `Ok(None)` without NLM_F_ACK_TLVS. Unless NLM_F_CAPPED is set
+    // the whole request is echoed, and the attributes only start after it.
+    pub fn parse(flags: u16, error: &ErrorMessage) -> Result<Option<Self>, DecodeError> {
+        if flags & NLM_F_ACK_TLVS == 0 {
+            return Ok(None);
+        }
+        let echo_len = if flags & NLM_F_CAPPED != 0 {
+            NETLINK_HEADER_LEN
+        } else {
+            NetlinkHeader::peek(&error.header)
+                .context("invalid request echoed in NLMSG_ERROR")?
+                .length as usize
+        };
+        let Some(attributes) = error.header.get((echo_len + 3) & !3..) else {
+            return Err(DecodeError::from(
+                "NLMSG_ERROR is too short for the request it echoes",
+            ));
+        };
+
+        let mut ext_ack = ExtAck::default();
+        for nla in NlasIterator::new(attributes) {
+            let nla = nla.context("invalid extended ack attribute")?;
+            match nla.kind() {
+                NLMSGERR_ATTR_MSG => {
+                    ext_ack.message =
+                        Some(parse_string(nla.value()).context("invalid NLMSGERR_ATTR_MSG")?)
+                }
+                NLMSGERR_ATTR_OFFS => {
+                    ext_ack.offset =
+                        Some(parse_u32(nla.value()).context("invalid NLMSGERR_ATTR_OFFS")?)
+                }
+                // The policy and missing attribute reports are not decoded.
+                _ => {}
+            }
+        }
+        Ok(Some(ext_ack))
+    }
+}
+
+// e.g. `Unknown conntrack family (at offset 20)`.
+impl fmt::Display for ExtAck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message.as_deref().unwrap_or("no message"))?;
+        if let Some(offset) = self.offset {
+            write!(f, " (at offset {})", offset)?;
+        }
+        Ok(())
+    }
+}
+
+// The ctnetlink (conntrack) protocol.
+pub mod conntrack;
+
+// Reading netlink traffic captured on an nlmon device.
+#[cfg(feature = "pcap")]
+pub mod pcap;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,163 @@
+use std::fmt;
+use std::io::{self, Read};
+
+use netlink_packet_core::{DecodeError, NetlinkMessage};
+
+use crate::{NetlinkProtocol, NetlinkStreamReader, route};
+
+// The link type of a capture taken on an nlmon device (`tcpdump -i nlmon0`).
+pub const LINKTYPE_NETLINK: u32 = 253;
+
+// The netlink family of conntrack and the other netfilter subsystems.
+pub const NETLINK_NETFILTER: u16 = 12;
+
+const PCAP_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+// libpcap captures nlmon in cooked mode: every packet starts with a
+// `struct sll_header`, whose last field is the netlink family.
+const SLL_HEADER_LEN: usize = 16;
+
+const MAGIC_MICROS: u32 = 0xa1b2c3d4;
+const MAGIC_NANOS: u32 = 0xa1b23c4d;
+
+// Everything that can go wrong while reading a capture.
+#[derive(Debug)]
+pub enum PcapError {
+    Io(io::Error),
+    // Not a pcap file, or not an nlmon one.
+    Format(String),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for PcapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PcapError::Io(e) => write!(f, "failed to read the capture: {}", e),
+            PcapError::Format(e) => write!(f, "invalid capture: {}", e),
+            PcapError::Decode(e) => write!(f, "failed to decode a captured message: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PcapError {}
+
+impl From<io::Error> for PcapError {
+    fn from(e: io::Error) -> Self {
+        PcapError::Io(e)
+    }
+}
+
+impl From<DecodeError> for PcapError {
+    fn from(e: DecodeError) -> Self {
+        PcapError::Decode(e)
+    }
+}
+
+// One captured datagram, which may hold several netlink messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NlmonPacket {
+    pub family: u16,
+    pub data: Vec<u8>,
+}
+
+// Reads the packets of a classic (not pcapng) nlmon capture. The file may
+// have been written on a host of either byte order.
+pub struct NlmonReader<R> {
+    input: R,
+    big_endian: bool,
+}
+
+impl<R: Read> NlmonReader<R> {
+    pub fn new(mut input: R) -> Result<Self, PcapError> {
+        let mut header = [0; PCAP_HEADER_LEN];
+        input.read_exact(&mut header)?;
+
+        let magic = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let big_endian = match magic {
+            MAGIC_MICROS | MAGIC_NANOS => false,
+            _ if magic.swap_bytes() == MAGIC_MICROS || magic.swap_bytes() == MAGIC_NANOS => true,
+            _ => return Err(PcapError::Format(format!("bad magic {:#010x}", magic))),
+        };
+        let reader = NlmonReader { input, big_endian };
+        let link_type = reader.u32_at(&header, 20);
+        if link_type != LINKTYPE_NETLINK {
+            return Err(PcapError::Format(format!(
+                "link type {} is not LINKTYPE_NETLINK",
+                link_type
+            )));
+        }
+        Ok(reader)
+    }
+
+    fn u32_at(&self, buf: &[u8], offset: usize) -> u32 {
+        let bytes = buf[offset..offset + 4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    // The next packet, `Ok(None)` at the end of the capture.
+    pub fn next_packet(&mut self) -> Result<Option<NlmonPacket>, PcapError> {
+        let mut header = [0; RECORD_HEADER_LEN];
+        match self.input.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let captured = self.u32_at(&header, 8) as usize;
+        let original = self.u32_at(&header, 12) as usize;
+        if captured < original {
+            return Err(PcapError::Format(format!(
+                "packet cut to {} of its {} bytes by the snapshot length",
+                captured, original
+            )));
+        }
+        if captured < SLL_HEADER_LEN {
+            return Err(PcapError::Format(format!(
+                "{} byte packet is too short for its cooked header",
+                captured
+            )));
+        }
+
+        let mut packet = vec![0; captured];
+        self.input.read_exact(&mut packet)?;
+        // The cooked header itself is always in network byte order.
+        let family = u16::from_be_bytes([packet[14], packet[15]]);
+        packet.drain(..SLL_HEADER_LEN);
+        Ok(Some(NlmonPacket {
+            family,
+            data: packet,
+        }))
+    }
+}
+
+// Decode every `P` message sent over the `family` netlink family, in both
+// directions. Messages of other protocols sharing the family (and
+// NLMSG_DONE, NLMSG_ERROR...) are skipped.
+pub fn read_messages<P: NetlinkProtocol, R: Read>(
+    input: R,
+    family: u16,
+) -> Result<Vec<NetlinkMessage<P>>, PcapError> {
+    let mut reader = NlmonReader::new(input)?;
+    let mut stream = NetlinkStreamReader::new();
+    let mut messages = Vec::new();
+    while let Some(packet) = reader.next_packet()? {
+        if packet.family != family {
+            continue;
+        }
+        stream.push(&packet.data);
+        while let Some(raw) = stream.next_raw()? {
+            messages.extend(route::<P>(&raw)?);
+        }
+    }
+    if stream.pending() != 0 {
+        return Err(DecodeError::from(format!(
+            "capture ends in the middle of a message, {} bytes left",
+            stream.pending()
+        ))
+        .into());
+    }
+    Ok(messages)
+}
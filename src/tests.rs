@@ -0,0 +1,144 @@
+use netlink_packet_core::{
+    Emitable, ErrorBuffer, ErrorMessage, NLM_F_CAPPED, NetlinkHeader, Nla, NlaBuffer, NlasIterator,
+    Parseable,
+};
+
+use crate::{ExtAck, KeyValue, NlaString, PeekHeader, StringPolicy};
+
+#[test]
+fn test_key_value_roundtrip() {
+    let nlas = vec![
+        KeyValue {
+            kind: 1,
+            value: vec![0x2a, 0x00, 0x00, 0x00],
+        },
+        // Needs 3 bytes of padding.
+        KeyValue {
+            kind: 7,
+            value: b"hello\0".to_vec(),
+        },
+        KeyValue {
+            kind: 0x1234,
+            value: vec![],
+        },
+    ];
+
+    let mut buf = vec![0xff; nlas.as_slice().buffer_len()];
+    nlas.as_slice().emit(&mut buf);
+
+    #[rustfmt::skip]
+    let expected = [
+        0x08, 0x00, 0x01, 0x00, 0x2a, 0x00, 0x00, 0x00,
+        0x0a, 0x00, 0x07, 0x00, b'h', b'e', b'l', b'l', b'o', 0x00, 0x00, 0x00,
+        0x04, 0x00, 0x34, 0x12,
+    ];
+    assert_eq!(buf, expected);
+
+    let parsed = NlasIterator::new(&buf)
+        .map(|nla| KeyValue::parse(&nla.unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(parsed, nlas);
+}
+
+#[test]
+fn test_ext_ack() {
+    #[rustfmt::skip]
+    let raw = [
+        // nlmsghdr: NLMSG_ERROR, NLM_F_CAPPED | NLM_F_ACK_TLVS
+        0x3c, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x03,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // -EINVAL
+        0xea, 0xff, 0xff, 0xff,
+        // The header of the request, without its payload.
+        0x24, 0x00, 0x00, 0x00, 0x01, 0x01, 0x05, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // NLMSGERR_ATTR_MSG "bad family"
+        0x0f, 0x00, 0x01, 0x00, b'b', b'a', b'd', b' ',
+        b'f', b'a', b'm', b'i', b'l', b'y', 0x00, 0x00,
+        // NLMSGERR_ATTR_OFFS 16
+        0x08, 0x00, 0x02, 0x00, 0x10, 0x00, 0x00, 0x00,
+    ];
+    let header = NetlinkHeader::peek(&raw).unwrap();
+    let payload = raw[16..].to_vec();
+    let error = ErrorMessage::parse(&ErrorBuffer::new_checked(&payload).unwrap()).unwrap();
+    assert_eq!(error.code.unwrap().get(), -22);
+
+    let ext_ack = ExtAck::parse(header.flags, &error).unwrap().unwrap();
+    assert_eq!(
+        ext_ack,
+        ExtAck {
+            message: Some("bad family".to_string()),
+            offset: Some(16),
+        }
+    );
+    assert_eq!(ext_ack.to_string(), "bad family (at offset 16)");
+
+    // Without NLM_F_ACK_TLVS there is nothing to look for.
+    assert_eq!(ExtAck::parse(NLM_F_CAPPED, &error).unwrap(), None);
+}
+
+#[test]
+fn test_string_policy() {
+    let terminated = NlaString {
+        kind: 3,
+        value: "Alice".to_string(),
+        policy: StringPolicy::NullTerminated,
+    };
+    let not_terminated = NlaString {
+        policy: StringPolicy::NotTerminated,
+        ..terminated.clone()
+    };
+    assert_eq!(terminated.value_len(), 6);
+    assert_eq!(not_terminated.value_len(), 5);
+
+    let mut buf = vec![0xff; terminated.buffer_len()];
+    terminated.emit(&mut buf);
+    assert_eq!(buf, b"\x0a\x00\x03\x00Alice\0\0\0");
+
+    let mut buf = vec![0xff; not_terminated.buffer_len()];
+    not_terminated.emit(&mut buf);
+    assert_eq!(buf, b"\x09\x00\x03\x00Alice\0\0\0");
+
+    // Both decode to the same string.
+    let parsed = NlaString::parse(&NlaBuffer::new_checked(&buf[..]).unwrap()).unwrap();
+    assert_eq!(parsed, terminated);
+}
+
+#[test]
+fn test_read_int() {
+    assert_eq!(read_int!(u8, &[0x2a]).unwrap(), 0x2a);
+    assert_eq!(read_int!(be u16, &[0x01, 0xbb]).unwrap(), 443);
+    assert_eq!(read_int!(u16, &443u16.to_ne_bytes()).unwrap(), 443);
+    assert_eq!(read_int!(be u32, &[0, 0, 0x01, 0x2c]).unwrap(), 300);
+    assert_eq!(read_int!(u32, &300u32.to_ne_bytes()).unwrap(), 300);
+    assert_eq!(
+        read_int!(be u64, &[0, 0, 0, 0, 0, 0, 0x10, 0]).unwrap(),
+        4096
+    );
+
+    // Too short and too long are both rejected, whatever the width.
+    let errors = [
+        read_int!(u8, &[]).map(u64::from),
+        read_int!(u8, &[1, 2]).map(u64::from),
+        read_int!(be u16, &[1]).map(u64::from),
+        read_int!(u16, &[1, 2, 3, 4]).map(u64::from),
+        read_int!(be u32, &[1, 2]).map(u64::from),
+        read_int!(u32, &[1, 2, 3, 4, 5, 6, 7, 8]).map(u64::from),
+        read_int!(be u64, &[1, 2, 3, 4]),
+        read_int!(u64, &[]),
+    ]
+    .map(|result| result.unwrap_err().to_string());
+    assert_eq!(
+        errors,
+        [
+            "expected 1 bytes, got 0",
+            "expected 1 bytes, got 2",
+            "expected 2 bytes, got 1",
+            "expected 2 bytes, got 4",
+            "expected 4 bytes, got 2",
+            "expected 4 bytes, got 8",
+            "expected 8 bytes, got 4",
+            "expected 8 bytes, got 0",
+        ]
+    );
+}